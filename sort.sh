@@ -1,2 +1,2 @@
 #!/bin/sh
-sort -bfio config/card-tags.toml config/card-tags.toml
\ No newline at end of file
+cargo run -q -- fmt && sort -bfio config/card-tags.toml config/card-tags.toml
\ No newline at end of file
@@ -1,9 +1,11 @@
 use crate::{
-    scryfall::{Card, CardList},
-    tags::{CardTags, TagIndex, TagRef},
+    oracle::{EntersTapped, FetchFilter, ManaAbility},
+    scryfall::{Card, CardFace, CardList, Layout},
+    symbols::{render_oracle_text, render_symbols},
+    tags::{slugify, CardTags, TagIndex, TagRef, TagSource},
 };
 use itertools::join;
-use log::trace;
+use log::{trace, warn};
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
     hash::{Hash, Hasher},
@@ -14,6 +16,7 @@ use std::{
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum CardType {
     Land,
+    ModalLand,
     Artifact,
     Creature,
     Enchantment,
@@ -28,13 +31,17 @@ pub struct TaggedCardDb<'a> {
     card_index: HashMap<CardId<'a>, TaggedCard<'a>>,
     tag_index: HashMap<TagRef<'a>, HashSet<CardId<'a>>>,
     type_tag_index: HashMap<CardType, HashSet<TagRef<'a>>>,
+    fetch_index: HashMap<CardId<'a>, Vec<CardId<'a>>>,
+    fetched_by_index: HashMap<CardId<'a>, Vec<CardId<'a>>>,
 }
 
 #[derive(Debug)]
 pub struct TaggedCard<'a> {
     card: &'a Card<'a>,
     tags: HashSet<TagRef<'a>>,
+    tag_sources: HashMap<TagRef<'a>, BTreeSet<TagSource>>,
     types: BTreeSet<CardType>,
+    traits: CardTraits,
     front_image_uri: &'a str,
     back_image_uri: Option<&'a str>,
 }
@@ -42,6 +49,23 @@ pub struct TaggedCard<'a> {
 #[derive(Debug)]
 pub struct CardId<'a>(&'a str);
 
+/// The name, mana cost and oracle text of one face of a card, rendered as HTML with mana symbols.
+#[derive(Debug)]
+pub struct FaceText<'a> {
+    pub name: &'a str,
+    pub mana_cost: String,
+    pub oracle_text: String,
+}
+
+/// Properties of a card read from the faces it can be played as, which tag rules match against.
+#[derive(Debug)]
+pub struct CardTraits {
+    type_line: String,
+    mana_abilities: Vec<ManaAbility>,
+    enters_tapped: EntersTapped,
+    fetches: Vec<FetchFilter>,
+}
+
 impl CardType {
     fn from_str(type_line: &str) -> BTreeSet<CardType> {
         let mut types = BTreeSet::new();
@@ -72,9 +96,35 @@ impl CardType {
         types
     }
 
+    /// The types of the faces a card can be played as, for a card outside the tagged card
+    /// database.
+    pub fn from_card(card: &Card) -> BTreeSet<CardType> {
+        CardType::from_type_lines(card.layout, &TaggedCard::type_lines(card))
+    }
+
+    fn from_type_lines(layout: Layout, type_lines: &[&str]) -> BTreeSet<CardType> {
+        let face_types: Vec<_> = type_lines.iter().map(|t| CardType::from_str(t)).collect();
+        let is_land = |types: &BTreeSet<CardType>| types.contains(&CardType::Land);
+        if layout == Layout::ModalDfc
+            && face_types.iter().any(is_land)
+            && !face_types.iter().all(is_land)
+        {
+            let mut types: BTreeSet<_> = face_types
+                .into_iter()
+                .filter(|types| !is_land(types))
+                .flatten()
+                .collect();
+            types.insert(CardType::ModalLand);
+            types
+        } else {
+            face_types.into_iter().flatten().collect()
+        }
+    }
+
     pub fn base_uri(self) -> &'static str {
         match self {
             CardType::Land => "lands.html",
+            CardType::ModalLand => "mdfc-lands.html",
             CardType::Artifact => "artifacts.html",
             CardType::Creature => "creatures.html",
             CardType::Enchantment => "enchantments.html",
@@ -88,6 +138,7 @@ impl CardType {
     pub fn all_base_uri(self) -> &'static str {
         match self {
             CardType::Land => "all-lands.html",
+            CardType::ModalLand => "all-mdfc-lands.html",
             CardType::Artifact => "all-artifacts.html",
             CardType::Creature => "all-creatures.html",
             CardType::Enchantment => "all-enchantments.html",
@@ -101,6 +152,7 @@ impl CardType {
     pub fn filter_class(self) -> &'static str {
         match self {
             CardType::Land => "mtg-filter-land",
+            CardType::ModalLand => "mtg-filter-mdfc-land",
             CardType::Artifact => "mtg-filter-artifact",
             CardType::Creature => "mtg-filter-creature",
             CardType::Enchantment => "mtg-filter-enchantment",
@@ -111,22 +163,25 @@ impl CardType {
         }
     }
 
-    pub fn link_suffix(self) -> &'static str {
+    /// The id of the type's section on index and tag pages.
+    pub fn anchor(self) -> &'static str {
         match self {
-            CardType::Land => "#Land",
-            CardType::Artifact => "#Artifact",
-            CardType::Creature => "#Creature",
-            CardType::Enchantment => "#Enchantment",
-            CardType::Instant => "#Instant",
-            CardType::Sorcery => "#Sorcery",
-            CardType::Planeswalker => "#Planeswalker",
-            CardType::Battle => "#Battle",
+            CardType::Land => "Land",
+            CardType::ModalLand => "MDFC-Land",
+            CardType::Artifact => "Artifact",
+            CardType::Creature => "Creature",
+            CardType::Enchantment => "Enchantment",
+            CardType::Instant => "Instant",
+            CardType::Sorcery => "Sorcery",
+            CardType::Planeswalker => "Planeswalker",
+            CardType::Battle => "Battle",
         }
     }
 
     pub fn plural(self) -> &'static str {
         match self {
             CardType::Land => "Lands",
+            CardType::ModalLand => "MDFC Lands",
             CardType::Artifact => "Artifacts",
             CardType::Creature => "Creatures",
             CardType::Enchantment => "Enchantments",
@@ -145,6 +200,7 @@ impl<'a> TaggedCardDb<'a> {
         let mut type_tag_index: HashMap<CardType, HashSet<TagRef<'a>>> = HashMap::new();
 
         type_tag_index.insert(CardType::Land, HashSet::new());
+        type_tag_index.insert(CardType::ModalLand, HashSet::new());
         type_tag_index.insert(CardType::Artifact, HashSet::new());
         type_tag_index.insert(CardType::Creature, HashSet::new());
         type_tag_index.insert(CardType::Enchantment, HashSet::new());
@@ -153,56 +209,136 @@ impl<'a> TaggedCardDb<'a> {
         type_tag_index.insert(CardType::Planeswalker, HashSet::new());
         type_tag_index.insert(CardType::Battle, HashSet::new());
 
-        for (tags, card) in cards
-            .cards()
+        let included_tags: Vec<_> = tag_index
             .iter()
-            .filter_map(|card| card_tags.get_tags(&card.name).map(|tags| (tags, card)))
-        {
-            trace!("tagging card '{}'", &card.name);
-            let type_line = join(
-                card.type_line.iter().chain(
-                    card.card_faces
-                        .iter()
-                        .flatten()
-                        .flat_map(|face| face.type_line.iter()),
-                ),
-                " ",
-            );
-            let types = CardType::from_str(&type_line);
+            .map(|(_, tag)| tag)
+            .filter(|tag| tag.is_included())
+            .collect();
 
-            let mut tags: HashSet<_> = tags
-                .iter()
-                .map(|tag| tag_index.get(&tag).expect(&format!("invalid tag {}", &tag)))
+        for (name, tag) in card_tags.unknown_exclusions(tag_index) {
+            warn!("card \"{}\" excludes unknown tag \"{}\"", name, tag);
+        }
+
+        for card in cards.cards() {
+            let type_lines = TaggedCard::type_lines(card);
+            let traits = CardTraits::new(card, &type_lines);
+            let excluded: Vec<_> = card_tags
+                .get_excluded_tags(&card.name)
+                .into_iter()
+                .filter_map(|tag| tag_index.get(tag))
                 .collect();
+            for tag in needless_exclusions(card, &traits, card_tags, tag_index) {
+                warn!(
+                    "card \"{}\" excludes tag \"{}\" which it does not have",
+                    &card.name,
+                    tag.name()
+                );
+            }
+            // Cards without tags of their own, including those whose entry only excludes tags,
+            // are added when an included tag they don't exclude matches them.
+            let tags = match card_tags.get_tags(&card.name) {
+                Some(tags) if !tags.is_empty() => tags,
+                _ if included_tags
+                    .iter()
+                    .any(|tag| !excluded.contains(tag) && tag.is_match(card, &traits)) =>
+                {
+                    Vec::new()
+                }
+                _ => continue,
+            };
+            trace!("tagging card '{}'", &card.name);
+            let types = CardType::from_type_lines(card.layout, &type_lines);
+
+            let mut tag_sources: HashMap<_, BTreeSet<_>> = HashMap::new();
+            for tag in tags {
+                let tag_ref = tag_index
+                    .get(tag)
+                    .unwrap_or_else(|| panic!("invalid tag {}", &tag));
+                tag_sources
+                    .entry(tag_ref)
+                    .or_default()
+                    .insert(TagSource::Manual);
+            }
             for (_, tag_ref) in tag_index.iter() {
-                if tag_ref.is_match(&card, &type_line) {
-                    tags.insert(tag_ref);
+                for source in tag_ref.match_rules(card, &traits) {
+                    tag_sources.entry(tag_ref).or_default().insert(source);
                 }
             }
+            let mut implying: Vec<_> = tag_sources.keys().copied().collect();
+            while let Some(tag_ref) = implying.pop() {
+                for implied in tag_ref
+                    .implies()
+                    .iter()
+                    .filter_map(|name| tag_index.get(name))
+                {
+                    if tag_sources
+                        .entry(implied)
+                        .or_default()
+                        .insert(TagSource::Implied)
+                    {
+                        implying.push(implied);
+                    }
+                }
+            }
+            for tag in &excluded {
+                tag_sources.remove(tag);
+            }
 
-            for tag in &tags {
+            for tag in tag_sources.keys() {
                 if let Some(ids) = card_tag_index.get_mut(tag) {
                     ids.insert(CardId::new(card.id.as_ref()));
                 } else {
                     let mut set = HashSet::new();
                     set.insert(CardId::new(card.id.as_ref()));
-                    card_tag_index.insert(tag.clone(), set);
+                    card_tag_index.insert(*tag, set);
                 }
 
                 for card_type in &types {
-                    type_tag_index
-                        .get_mut(card_type)
-                        .unwrap()
-                        .insert(tag.clone());
+                    type_tag_index.get_mut(card_type).unwrap().insert(*tag);
                 }
             }
-            let tagged_card = TaggedCard::new(card, tags, types);
+            let tagged_card = TaggedCard::new(card, tag_sources, types, traits);
             card_index.insert(CardId::new(tagged_card.card.id.as_ref()), tagged_card);
         }
+
+        // Searches that can find any land are left out, as they would link every land.
+        let mut fetch_index: HashMap<_, Vec<_>> = HashMap::new();
+        let mut fetched_by_index: HashMap<_, Vec<_>> = HashMap::new();
+        let lands: Vec<_> = card_index
+            .values()
+            .filter(|card| card.has_type(&CardType::Land))
+            .collect();
+        for fetcher in card_index.values() {
+            let filters: Vec<_> = fetcher
+                .fetches()
+                .iter()
+                .filter(|filter| !filter.is_any_land())
+                .collect();
+            if filters.is_empty() {
+                continue;
+            }
+            let fetcher_id = CardId::new(fetcher.card.id.as_ref());
+            for land in &lands {
+                if filters
+                    .iter()
+                    .any(|filter| filter.matches(land.type_line()))
+                {
+                    let land_id = CardId::new(land.card.id.as_ref());
+                    fetch_index.entry(fetcher_id).or_default().push(land_id);
+                    fetched_by_index
+                        .entry(land_id)
+                        .or_default()
+                        .push(fetcher_id);
+                }
+            }
+        }
+
         TaggedCardDb {
             card_index,
             tag_index: card_tag_index,
             type_tag_index,
+            fetch_index,
+            fetched_by_index,
         }
     }
 
@@ -218,48 +354,202 @@ impl<'a> TaggedCardDb<'a> {
         &self.tag_index
     }
 
+    pub fn find_by_name(&self, name: &str) -> Option<&TaggedCard<'a>> {
+        self.card_index
+            .values()
+            .find(|card| card.card.name.eq_ignore_ascii_case(name))
+    }
+
     pub fn type_has_cards_of_tag(&self, card_type: &CardType, tag: &'a TagRef<'a>) -> bool {
         self.type_tag_index[card_type].contains(tag)
     }
+
+    /// The tagged lands a card can search its library for.
+    pub fn can_fetch(&self, card: &TaggedCard<'a>) -> Vec<&TaggedCard<'a>> {
+        self.related_cards(&self.fetch_index, std::iter::once(card.id()))
+    }
+
+    /// The tagged cards that can search their library for a land.
+    pub fn fetchable_by(&self, card: &TaggedCard<'a>) -> Vec<&TaggedCard<'a>> {
+        self.related_cards(&self.fetched_by_index, std::iter::once(card.id()))
+    }
+
+    /// The tagged lands any card with a tag can search its library for.
+    pub fn tag_can_fetch(&self, tag: &TagRef<'a>) -> Vec<&TaggedCard<'a>> {
+        self.related_cards(
+            &self.fetch_index,
+            self.tag_index.get(tag).into_iter().flatten().copied(),
+        )
+    }
+
+    /// The tagged cards that can search their library for any land with a tag.
+    pub fn tag_fetchable_by(&self, tag: &TagRef<'a>) -> Vec<&TaggedCard<'a>> {
+        self.related_cards(
+            &self.fetched_by_index,
+            self.tag_index.get(tag).into_iter().flatten().copied(),
+        )
+    }
+
+    fn related_cards(
+        &self,
+        index: &HashMap<CardId<'a>, Vec<CardId<'a>>>,
+        ids: impl Iterator<Item = CardId<'a>>,
+    ) -> Vec<&TaggedCard<'a>> {
+        let mut cards: Vec<_> = ids
+            .filter_map(|id| index.get(&id))
+            .flatten()
+            .filter_map(|id| self.card_index.get(id))
+            .collect();
+        cards.sort_unstable_by_key(|card| &card.card.name);
+        cards.dedup_by_key(|card| card.card.id.as_ref());
+        cards
+    }
+}
+
+// The tags a card's entry excludes that it wouldn't have anyway, as neither its entry lists them
+// nor do their rules match it.
+fn needless_exclusions<'t>(
+    card: &Card,
+    traits: &CardTraits,
+    card_tags: &CardTags,
+    tag_index: &'t TagIndex,
+) -> Vec<TagRef<'t>> {
+    let listed: Vec<_> = card_tags
+        .get_tags(&card.name)
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag_index.get(tag))
+        .collect();
+    card_tags
+        .get_excluded_tags(&card.name)
+        .into_iter()
+        .filter_map(|tag| tag_index.get(tag))
+        .filter(|tag| !listed.contains(tag) && !tag.is_match(card, traits))
+        .collect()
+}
+
+impl CardTraits {
+    /// Reads the traits of a card outside the tagged card database.
+    pub fn from_card(card: &Card) -> CardTraits {
+        CardTraits::new(card, &TaggedCard::type_lines(card))
+    }
+
+    fn new(card: &Card, type_lines: &[&str]) -> CardTraits {
+        let oracle_texts = TaggedCard::oracle_texts(card);
+        CardTraits {
+            type_line: join(type_lines, " // "),
+            mana_abilities: oracle_texts
+                .iter()
+                .flat_map(|text| ManaAbility::parse_all(text))
+                .collect(),
+            enters_tapped: oracle_texts
+                .iter()
+                .map(|text| EntersTapped::parse(text))
+                .find(|enters_tapped| *enters_tapped != EntersTapped::Untapped)
+                .unwrap_or_default(),
+            fetches: oracle_texts
+                .iter()
+                .flat_map(|text| FetchFilter::parse_all(text))
+                .collect(),
+        }
+    }
+
+    pub fn type_line(&self) -> &str {
+        &self.type_line
+    }
+
+    pub fn mana_abilities(&self) -> &[ManaAbility] {
+        &self.mana_abilities
+    }
+
+    pub fn enters_tapped(&self) -> &EntersTapped {
+        &self.enters_tapped
+    }
+
+    pub fn fetches(&self) -> &[FetchFilter] {
+        &self.fetches
+    }
 }
 
 impl<'a> TaggedCard<'a> {
-    fn new(card: &'a Card<'a>, tags: HashSet<TagRef<'a>>, types: BTreeSet<CardType>) -> Self {
-        let back_image_uri = card
-            .card_faces
-            .as_ref()
-            .and_then(|v| v.get(1))
-            .and_then(|f| f.image_uris.as_ref())
-            .filter(|_| card.image_uris.is_none())
-            .and_then(|m| m.get("normal"))
-            .map(|c| c.as_ref());
-        let front_image_uri = if back_image_uri.is_some() {
+    fn new(
+        card: &'a Card<'a>,
+        tag_sources: HashMap<TagRef<'a>, BTreeSet<TagSource>>,
+        types: BTreeSet<CardType>,
+        traits: CardTraits,
+    ) -> Self {
+        let face_image_uri = |i: usize| {
             card.card_faces
                 .as_ref()
-                .unwrap()
-                .get(0)
+                .and_then(|v| v.get(i))
                 .and_then(|f| f.image_uris.as_ref())
                 .and_then(|m| m.get("normal"))
                 .map(|c| c.as_ref())
-                .unwrap_or("")
+        };
+        let card_image_uri = card
+            .image_uris
+            .as_ref()
+            .and_then(|m| m.get("normal"))
+            .map(|c| c.as_ref());
+        let (front_image_uri, back_image_uri) = if card.layout.has_face_images() {
+            (face_image_uri(0).or(card_image_uri), face_image_uri(1))
         } else {
-            card.image_uris
-                .as_ref()
-                .and_then(|m| m.get("normal"))
-                .map(|c| c.as_ref())
-                .unwrap_or("")
+            (card_image_uri.or_else(|| face_image_uri(0)), None)
         };
         TaggedCard {
             card,
-            tags,
+            tags: tag_sources.keys().copied().collect(),
+            tag_sources,
             types,
-            front_image_uri,
+            traits,
+            front_image_uri: front_image_uri.unwrap_or(""),
             back_image_uri,
         }
     }
 
+    // The faces whose characteristics the card has outside the stack. Split cards have the
+    // combined types of both halves, and either face of a modal double-faced card can be played.
+    fn playable_faces(card: &'a Card<'a>) -> &'a [CardFace<'a>] {
+        let faces = card.card_faces.as_deref().unwrap_or_default();
+        match card.layout {
+            Layout::Split | Layout::ModalDfc => faces,
+            Layout::Transform
+            | Layout::Flip
+            | Layout::Adventure
+            | Layout::Meld
+            | Layout::Battle
+            | Layout::ReversibleCard
+            | Layout::DoubleFacedToken => &faces[..faces.len().min(1)],
+            Layout::Normal | Layout::Other => &[],
+        }
+    }
+
+    fn type_lines(card: &'a Card<'a>) -> Vec<&'a str> {
+        let type_lines: Vec<_> = TaggedCard::playable_faces(card)
+            .iter()
+            .filter_map(|face| face.type_line.as_deref())
+            .collect();
+        if type_lines.is_empty() {
+            card.type_line.as_deref().into_iter().collect()
+        } else {
+            type_lines
+        }
+    }
+
+    fn oracle_texts(card: &'a Card<'a>) -> Vec<&'a str> {
+        let oracle_texts: Vec<_> = TaggedCard::playable_faces(card)
+            .iter()
+            .filter_map(|face| face.oracle_text.as_deref())
+            .collect();
+        if oracle_texts.is_empty() {
+            card.oracle_text.as_deref().into_iter().collect()
+        } else {
+            oracle_texts
+        }
+    }
+
     pub fn card(&self) -> &Card<'a> {
-        &self.card
+        self.card
     }
 
     pub fn tags(&self) -> Vec<TagRef<'a>> {
@@ -272,10 +562,112 @@ impl<'a> TaggedCard<'a> {
         &self.tags
     }
 
+    pub fn explain_tag(&self, tag: &TagRef<'a>) -> String {
+        join(
+            self.tag_sources
+                .get(tag)
+                .into_iter()
+                .flatten()
+                .map(|&source| {
+                    let explanation = match source {
+                        TagSource::Implied => format!("by {}", self.implying_tags(tag)),
+                        _ => tag.explain(source, self.card, &self.traits),
+                    };
+                    format!("{}: {}", source, explanation)
+                }),
+            "; ",
+        )
+    }
+
+    // The card's tags that imply a tag, quoted and sorted by name.
+    fn implying_tags(&self, tag: &TagRef<'a>) -> String {
+        let mut names: Vec<_> = self
+            .tag_sources
+            .keys()
+            .filter(|other| other.implies_tag(tag))
+            .map(|other| format!("\"{}\"", other.name()))
+            .collect();
+        names.sort_unstable();
+        join(names, ", ")
+    }
+
     pub fn types(&self) -> &BTreeSet<CardType> {
         &self.types
     }
 
+    pub fn traits(&self) -> &CardTraits {
+        &self.traits
+    }
+
+    pub fn type_line(&self) -> &str {
+        self.traits.type_line()
+    }
+
+    pub fn mana_abilities(&self) -> &[ManaAbility] {
+        self.traits.mana_abilities()
+    }
+
+    pub fn enters_tapped(&self) -> &EntersTapped {
+        self.traits.enters_tapped()
+    }
+
+    pub fn fetches(&self) -> &[FetchFilter] {
+        self.traits.fetches()
+    }
+
+    pub fn id(&self) -> CardId<'a> {
+        CardId::new(self.card.id.as_ref())
+    }
+
+    /// The page name of the card, `card-{slug}.html`.
+    pub fn slug(&self) -> String {
+        slugify(&self.card.name)
+    }
+
+    /// The card's mana cost as HTML, with the costs of its faces separated by `//`.
+    pub fn mana_cost_html(&self) -> String {
+        match &self.card.mana_cost {
+            Some(mana_cost) => render_symbols(mana_cost),
+            None => join(
+                self.card
+                    .card_faces
+                    .iter()
+                    .flatten()
+                    .filter_map(|face| face.mana_cost.as_deref())
+                    .filter(|mana_cost| !mana_cost.is_empty())
+                    .map(render_symbols),
+                " // ",
+            ),
+        }
+    }
+
+    /// Each face's text, or just the card's when its faces don't have their own.
+    pub fn face_texts(&self) -> Vec<FaceText<'a>> {
+        let faces: Vec<_> = self
+            .card
+            .card_faces
+            .iter()
+            .flatten()
+            .filter(|face| face.oracle_text.is_some())
+            .map(|face| FaceText {
+                name: &face.name,
+                mana_cost: render_symbols(face.mana_cost.as_deref().unwrap_or_default()),
+                oracle_text: render_oracle_text(face.oracle_text.as_deref().unwrap_or_default()),
+            })
+            .collect();
+        if faces.is_empty() {
+            vec![FaceText {
+                name: &self.card.name,
+                mana_cost: self.mana_cost_html(),
+                oracle_text: render_oracle_text(
+                    self.card.oracle_text.as_deref().unwrap_or_default(),
+                ),
+            }]
+        } else {
+            faces
+        }
+    }
+
     pub fn front_image_uri(&self) -> &str {
         self.front_image_uri
     }
@@ -301,7 +693,7 @@ impl<'a> CardId<'a> {
 
 impl<'a> Clone for CardId<'a> {
     fn clone(&self) -> CardId<'a> {
-        CardId(self.0)
+        *self
     }
 }
 
@@ -341,6 +733,7 @@ impl std::fmt::Display for CardType {
             "{}",
             match self {
                 CardType::Land => "Land",
+                CardType::ModalLand => "MDFC Land",
                 CardType::Artifact => "Artifact",
                 CardType::Creature => "Creature",
                 CardType::Enchantment => "Enchantment",
@@ -352,3 +745,341 @@ impl std::fmt::Display for CardType {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn config_path(file: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("config")
+            .join(file)
+    }
+
+    static SNOW_COVERED_WASTES: &str = r#"[{
+        "id": "00000000-0000-0000-0000-000000000001",
+        "scryfall_uri": "https://scryfall.com/card/csp/155",
+        "cmc": 0.0,
+        "color_identity": [],
+        "name": "Snow-Covered Wastes",
+        "type_line": "Basic Snow Land",
+        "oracle_text": "({T}: Add {C}.)",
+        "produced_mana": ["C"],
+        "set_type": "expansion",
+        "image_uris": {"normal": "https://cards.scryfall.io/normal/wastes.jpg"}
+    }]"#;
+
+    #[test]
+    fn unlisted_snow_basic_is_included() {
+        let tag_index = TagIndex::load(&config_path("tags.toml")).unwrap();
+        let card_tags = CardTags::load(&config_path("card-tags.toml")).unwrap();
+        assert!(card_tags.get_tags("Snow-Covered Wastes").is_none());
+
+        let cards: CardList = serde_json::from_str(SNOW_COVERED_WASTES).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+
+        let card = carddb.find_by_name("Snow-Covered Wastes").unwrap();
+        let snow_basic = tag_index.get("Snow Basic").unwrap();
+        assert!(card.tag_set().contains(&snow_basic));
+        assert!(card.tag_set().contains(&tag_index.get("Basic").unwrap()));
+        assert_eq!(
+            card.explain_tag(&snow_basic),
+            "type regex: matches 'Basic Snow'"
+        );
+    }
+
+    static TUNDRA: &str = r#"[{
+        "id": "00000000-0000-0000-0000-000000000002",
+        "scryfall_uri": "https://scryfall.com/card/lea/285",
+        "cmc": 0.0,
+        "color_identity": ["W", "U"],
+        "name": "Tundra",
+        "type_line": "Land — Plains Island",
+        "oracle_text": "({T}: Add {W} or {U}.)",
+        "produced_mana": ["W", "U"],
+        "set_type": "core",
+        "image_uris": {"normal": "https://cards.scryfall.io/normal/tundra.jpg"}
+    }]"#;
+
+    static POLLUTED_DELTA: &str = r#"[{
+        "id": "00000000-0000-0000-0000-000000000003",
+        "scryfall_uri": "https://scryfall.com/card/ons/321",
+        "cmc": 0.0,
+        "color_identity": [],
+        "name": "Polluted Delta",
+        "type_line": "Land",
+        "oracle_text": "{T}, Pay 1 life, Sacrifice Polluted Delta: Search your library for an Island or Swamp card, put it onto the battlefield, then shuffle.",
+        "set_type": "expansion",
+        "image_uris": {"normal": "https://cards.scryfall.io/normal/delta.jpg"}
+    }]"#;
+
+    #[test]
+    fn implied_tags() {
+        let tag_index = TagIndex::load(&config_path("tags.toml")).unwrap();
+        let card_tags: CardTags =
+            toml::from_str(r#""Polluted Delta" = ["Fast Fetch", "Fetch"]"#).unwrap();
+        let cards: CardList = serde_json::from_str(POLLUTED_DELTA).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+
+        let card = carddb.find_by_name("Polluted Delta").unwrap();
+        assert_eq!(
+            card.explain_tag(&tag_index.get("Fetch").unwrap()),
+            "manual: listed in card-tags.toml; implied: by \"Fast Fetch\""
+        );
+        assert_eq!(
+            card.explain_tag(&tag_index.get("Sac Land").unwrap()),
+            "implied: by \"Fast Fetch\""
+        );
+    }
+
+    #[test]
+    fn unknown_excluded_tags() {
+        let tag_index = TagIndex::load(&config_path("tags.toml")).unwrap();
+        let card_tags: CardTags = toml::from_str(
+            r#"
+            "Tundra" = ["-Snow Basci"]
+            "Snow-Covered Wastes" = ["-Snow Basic", "-No Such Tag"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            card_tags.unknown_exclusions(&tag_index),
+            [
+                ("Snow-Covered Wastes", "No Such Tag"),
+                ("Tundra", "Snow Basci")
+            ]
+        );
+
+        // An unknown tag is only reported as unknown.
+        let cards: CardList = serde_json::from_str(TUNDRA).unwrap();
+        let card = &cards.cards()[0];
+        let traits = CardTraits::from_card(card);
+        assert!(needless_exclusions(card, &traits, &card_tags, &tag_index).is_empty());
+    }
+
+    #[test]
+    fn needless_excluded_tags() {
+        let tag_index = TagIndex::load(&config_path("tags.toml")).unwrap();
+        let card_tags: CardTags = toml::from_str(
+            r#"
+            "Tundra" = ["-Snow Basic"]
+            "Snow-Covered Wastes" = ["-Snow Basic"]
+            "#,
+        )
+        .unwrap();
+        assert!(card_tags.unknown_exclusions(&tag_index).is_empty());
+
+        // No included tag matches Tundra, so it never enters the database.
+        let cards: CardList = serde_json::from_str(TUNDRA).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        assert!(carddb.find_by_name("Tundra").is_none());
+        let card = &cards.cards()[0];
+        let traits = CardTraits::from_card(card);
+        assert_eq!(
+            needless_exclusions(card, &traits, &card_tags, &tag_index),
+            [tag_index.get("Snow Basic").unwrap()]
+        );
+
+        let cards: CardList = serde_json::from_str(SNOW_COVERED_WASTES).unwrap();
+        let card = &cards.cards()[0];
+        let traits = CardTraits::from_card(card);
+        assert!(needless_exclusions(card, &traits, &card_tags, &tag_index).is_empty());
+    }
+
+    #[test]
+    fn excluding_the_included_tag_leaves_card_out() {
+        let tag_index = TagIndex::load(&config_path("tags.toml")).unwrap();
+        let cards: CardList = serde_json::from_str(SNOW_COVERED_WASTES).unwrap();
+
+        let card_tags: CardTags =
+            toml::from_str(r#""Snow-Covered Wastes" = ["-Snow Basic"]"#).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        assert!(carddb.find_by_name("Snow-Covered Wastes").is_none());
+
+        let card_tags: CardTags = toml::from_str(r#""Snow-Covered Wastes" = ["-Basic"]"#).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        let card = carddb.find_by_name("Snow-Covered Wastes").unwrap();
+        assert!(card
+            .tag_set()
+            .contains(&tag_index.get("Snow Basic").unwrap()));
+        assert!(!card.tag_set().contains(&tag_index.get("Basic").unwrap()));
+    }
+
+    static LAYOUTS: &str = r#"[
+        {
+            "id": "00000000-0000-0000-0000-000000000010",
+            "scryfall_uri": "https://scryfall.com/card/znr/90",
+            "cmc": 3.0,
+            "color_identity": ["B"],
+            "name": "Agadeem's Awakening // Agadeem, the Undercrypt",
+            "layout": "modal_dfc",
+            "set_type": "expansion",
+            "card_faces": [
+                {
+                    "name": "Agadeem's Awakening",
+                    "mana_cost": "{X}{B}{B}{B}",
+                    "type_line": "Sorcery",
+                    "oracle_text": "Return from your graveyard to the battlefield any number of target creature cards that each have a different mana value X or less.",
+                    "image_uris": {"normal": "https://cards.scryfall.io/normal/front/agadeem.jpg"}
+                },
+                {
+                    "name": "Agadeem, the Undercrypt",
+                    "mana_cost": "",
+                    "type_line": "Land",
+                    "oracle_text": "As Agadeem, the Undercrypt enters the battlefield, you may pay 3 life. If you don't, it enters the battlefield tapped.\n{T}: Add {B}.",
+                    "image_uris": {"normal": "https://cards.scryfall.io/normal/back/agadeem.jpg"}
+                }
+            ]
+        },
+        {
+            "id": "00000000-0000-0000-0000-000000000011",
+            "scryfall_uri": "https://scryfall.com/card/xln/76",
+            "cmc": 2.0,
+            "color_identity": ["U"],
+            "name": "Search for Azcanta // Azcanta, the Sunken Ruin",
+            "layout": "transform",
+            "set_type": "expansion",
+            "card_faces": [
+                {
+                    "name": "Search for Azcanta",
+                    "mana_cost": "{1}{U}",
+                    "type_line": "Legendary Enchantment",
+                    "oracle_text": "At the beginning of your upkeep, look at the top card of your library. You may put it into your graveyard. Then if you have seven or more cards in your graveyard, you may transform Search for Azcanta.",
+                    "image_uris": {"normal": "https://cards.scryfall.io/normal/front/azcanta.jpg"}
+                },
+                {
+                    "name": "Azcanta, the Sunken Ruin",
+                    "mana_cost": "",
+                    "type_line": "Legendary Land",
+                    "oracle_text": "(Transforms from Search for Azcanta.)\n{T}: Add {U}.",
+                    "image_uris": {"normal": "https://cards.scryfall.io/normal/back/azcanta.jpg"}
+                }
+            ]
+        },
+        {
+            "id": "00000000-0000-0000-0000-000000000012",
+            "scryfall_uri": "https://scryfall.com/card/eld/115",
+            "cmc": 3.0,
+            "color_identity": ["R"],
+            "name": "Bonecrusher Giant // Stomp",
+            "layout": "adventure",
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/front/bonecrusher.jpg"},
+            "card_faces": [
+                {
+                    "name": "Bonecrusher Giant",
+                    "mana_cost": "{2}{R}",
+                    "type_line": "Creature — Giant",
+                    "oracle_text": "Whenever Bonecrusher Giant becomes the target of a spell, Bonecrusher Giant deals 2 damage to that spell's controller."
+                },
+                {
+                    "name": "Stomp",
+                    "mana_cost": "{1}{R}",
+                    "type_line": "Instant — Adventure",
+                    "oracle_text": "Damage can't be prevented this turn. Stomp deals 2 damage to any target."
+                }
+            ]
+        },
+        {
+            "id": "00000000-0000-0000-0000-000000000013",
+            "scryfall_uri": "https://scryfall.com/card/gk1/86",
+            "cmc": 2.0,
+            "color_identity": ["B", "G", "U"],
+            "name": "Discovery // Dispersal",
+            "layout": "split",
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/front/discovery.jpg"},
+            "card_faces": [
+                {
+                    "name": "Discovery",
+                    "mana_cost": "{1}{U}",
+                    "type_line": "Sorcery",
+                    "oracle_text": "Surveil 2, then draw a card."
+                },
+                {
+                    "name": "Dispersal",
+                    "mana_cost": "{3}{U}{B}",
+                    "type_line": "Instant",
+                    "oracle_text": "Each opponent returns a nonland permanent they control with the highest mana value among permanents they control to its owner's hand, then discards a card."
+                }
+            ]
+        }
+    ]"#;
+
+    fn tagged<'a>(card: &'a Card<'a>) -> TaggedCard<'a> {
+        TaggedCard::new(
+            card,
+            HashMap::new(),
+            CardType::from_card(card),
+            CardTraits::from_card(card),
+        )
+    }
+
+    fn types(card: &TaggedCard) -> Vec<CardType> {
+        card.types().iter().copied().collect()
+    }
+
+    #[test]
+    fn modal_dfc_with_land_back() {
+        let cards: CardList = serde_json::from_str(LAYOUTS).unwrap();
+        let card = &cards.cards()[0];
+        assert!(card.image_uris.is_none());
+        let tagged = tagged(card);
+        assert_eq!(TaggedCard::type_lines(card), ["Sorcery", "Land"]);
+        assert_eq!(types(&tagged), [CardType::ModalLand, CardType::Sorcery]);
+        assert_eq!(
+            tagged.front_image_uri(),
+            "https://cards.scryfall.io/normal/front/agadeem.jpg"
+        );
+        assert_eq!(
+            tagged.back_image_uri(),
+            Some("https://cards.scryfall.io/normal/back/agadeem.jpg")
+        );
+        assert_eq!(
+            tagged.traits().enters_tapped(),
+            &EntersTapped::UnlessPayLife(3)
+        );
+    }
+
+    #[test]
+    fn transform_card_is_its_front_face() {
+        let cards: CardList = serde_json::from_str(LAYOUTS).unwrap();
+        let card = &cards.cards()[1];
+        assert!(card.image_uris.is_none());
+        let tagged = tagged(card);
+        assert_eq!(TaggedCard::type_lines(card), ["Legendary Enchantment"]);
+        assert_eq!(types(&tagged), [CardType::Enchantment]);
+        assert_eq!(
+            tagged.front_image_uri(),
+            "https://cards.scryfall.io/normal/front/azcanta.jpg"
+        );
+        assert_eq!(
+            tagged.back_image_uri(),
+            Some("https://cards.scryfall.io/normal/back/azcanta.jpg")
+        );
+    }
+
+    #[test]
+    fn adventure_and_split_cards() {
+        let cards: CardList = serde_json::from_str(LAYOUTS).unwrap();
+
+        let adventure = tagged(&cards.cards()[2]);
+        assert_eq!(TaggedCard::type_lines(adventure.card), ["Creature — Giant"]);
+        assert_eq!(types(&adventure), [CardType::Creature]);
+        assert_eq!(
+            adventure.front_image_uri(),
+            "https://cards.scryfall.io/normal/front/bonecrusher.jpg"
+        );
+        assert_eq!(adventure.back_image_uri(), None);
+
+        let split = tagged(&cards.cards()[3]);
+        assert_eq!(TaggedCard::type_lines(split.card), ["Sorcery", "Instant"]);
+        assert_eq!(types(&split), [CardType::Instant, CardType::Sorcery]);
+        assert_eq!(
+            split.front_image_uri(),
+            "https://cards.scryfall.io/normal/front/discovery.jpg"
+        );
+        assert_eq!(split.back_image_uri(), None);
+    }
+}
@@ -0,0 +1,362 @@
+use crate::{color::Color, deck::Deck, deck::DeckCard, simulate::ManaRequirement, tags::TagKind};
+use std::cmp::Ordering;
+
+const HAND_SIZE: u32 = 7;
+
+/// How well a split of basic lands supports the spells of one color.
+#[derive(Debug)]
+pub struct ColorCastability<'d> {
+    pub color: Color,
+    /// The spell least likely to be castable on curve with the recommended split, with its pips
+    /// of the color and the turn it's cast on.
+    pub hardest: (&'d str, u32, u32),
+    pub nonbasic_sources: u32,
+    pub current_basics: u32,
+    pub basics: u32,
+    pub current_castability: f64,
+    pub castability: f64,
+}
+
+/// Chooses how many of each basic land to run alongside a deck's nonbasic lands, maximizing the
+/// castability of its hardest-to-cast color. A spell is castable on curve when enough sources of
+/// each of its colors are among the cards seen by the turn its mana value allows, counting the
+/// nonbasic lands tagged with a mana pool of that color and the basic lands of its type.
+pub fn optimize_basics<'d>(
+    deck: &Deck<'d, '_>,
+    basics: u32,
+    on_the_draw: bool,
+) -> Vec<ColorCastability<'d>> {
+    let deck_size = deck.len();
+    let ln_factorials = ln_factorials(deck_size);
+    let mut rows = Vec::new();
+    let mut tables = Vec::new();
+    let mut all_demands = Vec::new();
+    for color in Color::ALL {
+        let demands: Vec<_> = deck
+            .cards()
+            .iter()
+            .filter(|deck_card| !deck_card.is_land())
+            .filter_map(|deck_card| {
+                let card = deck_card.card()?;
+                let requirement = ManaRequirement::from_mana_cost(card.front_mana_cost()?);
+                let pips = requirement.pips_of(color);
+                let turn = (requirement.total() as u32).max(1);
+                Some((card.name.as_ref(), pips, turn)).filter(|_| pips > 0)
+            })
+            .collect();
+        if demands.is_empty() {
+            continue;
+        }
+
+        // The castability of the color's hardest spell for each possible number of sources.
+        let table: Vec<(f64, usize)> = (0..=deck_size)
+            .map(|sources| {
+                demands
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(_, pips, turn))| {
+                        let seen = (HAND_SIZE + turn - 1 + on_the_draw as u32).min(deck_size);
+                        let odds = at_least(&ln_factorials, deck_size, sources, seen, pips);
+                        (odds, i)
+                    })
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+                    .unwrap()
+            })
+            .collect();
+
+        // Untagged lands have no mana pool tags, so their produced mana is used instead.
+        let produces = |deck_card: &&DeckCard| match deck_card.tagged() {
+            Some(card) => card.tags().iter().any(|tag| {
+                *tag.kind().kind() == TagKind::ManaPool
+                    && tag.mana().filter(|mana| mana.provides(color)).is_some()
+            }),
+            None => deck_card
+                .card()
+                .is_some_and(|card| card.produced_colors().contains(&color)),
+        };
+        let nonbasic_sources: u32 = deck
+            .cards()
+            .iter()
+            .filter(|deck_card| deck_card.is_land() && !deck_card.is_basic_land())
+            .filter(produces)
+            .map(|deck_card| deck_card.count())
+            .sum();
+        let current_basics: u32 = deck
+            .cards()
+            .iter()
+            .filter(|deck_card| deck_card.is_basic_land())
+            .filter(|deck_card| {
+                deck_card
+                    .traits()
+                    .is_some_and(|traits| traits.type_line().contains(basic_land_type(color)))
+            })
+            .map(|deck_card| deck_card.count())
+            .sum();
+
+        let current = table[(nonbasic_sources + current_basics).min(deck_size) as usize];
+        rows.push(ColorCastability {
+            color,
+            hardest: demands[current.1],
+            nonbasic_sources,
+            current_basics,
+            basics: 0,
+            current_castability: current.0,
+            castability: 0.0,
+        });
+        tables.push(table);
+        all_demands.push(demands);
+    }
+
+    let mut split = vec![0; rows.len()];
+    let mut best = (vec![0; rows.len()], f64::MIN, f64::MIN);
+    search_splits(&rows, &tables, deck_size, basics, 0, &mut split, &mut best);
+    for (i, row) in rows.iter_mut().enumerate() {
+        let (odds, hardest) = tables[i][(row.nonbasic_sources + best.0[i]).min(deck_size) as usize];
+        row.basics = best.0[i];
+        row.castability = odds;
+        row.hardest = all_demands[i][hardest];
+    }
+    rows
+}
+
+// Tries every way of dividing the remaining basics among the colors from `index` on, keeping the
+// split with the best worst-color castability, then the best total.
+fn search_splits(
+    rows: &[ColorCastability],
+    tables: &[Vec<(f64, usize)>],
+    deck_size: u32,
+    remaining: u32,
+    index: usize,
+    split: &mut Vec<u32>,
+    best: &mut (Vec<u32>, f64, f64),
+) {
+    if index + 1 >= rows.len() {
+        if let Some(last) = split.last_mut() {
+            *last = remaining;
+        }
+        let odds: Vec<f64> = rows
+            .iter()
+            .zip(split.iter())
+            .zip(tables)
+            .map(|((row, basics), table)| {
+                table[(row.nonbasic_sources + basics).min(deck_size) as usize].0
+            })
+            .collect();
+        let worst = odds.iter().copied().fold(f64::MAX, f64::min);
+        let total = odds.iter().sum();
+        if (worst, total) > (best.1, best.2) {
+            *best = (split.clone(), worst, total);
+        }
+        return;
+    }
+    for basics in 0..=remaining {
+        split[index] = basics;
+        search_splits(
+            rows,
+            tables,
+            deck_size,
+            remaining - basics,
+            index + 1,
+            split,
+            best,
+        );
+    }
+}
+
+pub fn basic_land_type(color: Color) -> &'static str {
+    match color {
+        Color::White => "Plains",
+        Color::Blue => "Island",
+        Color::Black => "Swamp",
+        Color::Red => "Mountain",
+        Color::Green => "Forest",
+    }
+}
+
+fn ln_factorials(n: u32) -> Vec<f64> {
+    let mut ln_factorials = vec![0.0; n as usize + 1];
+    for i in 1..=n as usize {
+        ln_factorials[i] = ln_factorials[i - 1] + (i as f64).ln();
+    }
+    ln_factorials
+}
+
+fn ln_choose(ln_factorials: &[f64], n: u32, k: u32) -> f64 {
+    ln_factorials[n as usize] - ln_factorials[k as usize] - ln_factorials[(n - k) as usize]
+}
+
+/// The hypergeometric probability of at least `wanted` of `successes` cards among `draws` cards
+/// drawn from a deck of `population`.
+fn at_least(
+    ln_factorials: &[f64],
+    population: u32,
+    successes: u32,
+    draws: u32,
+    wanted: u32,
+) -> f64 {
+    let successes = successes.min(population);
+    let total = ln_choose(ln_factorials, population, draws);
+    (wanted..=successes.min(draws))
+        .filter(|drawn| draws - drawn <= population - successes)
+        .map(|drawn| {
+            (ln_choose(ln_factorials, successes, drawn)
+                + ln_choose(ln_factorials, population - successes, draws - drawn)
+                - total)
+                .exp()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        card::TaggedCardDb,
+        deck::Decklist,
+        scryfall::CardList,
+        tags::{CardTags, TagIndex},
+    };
+    use std::path::Path;
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn hypergeometric_odds() {
+        let ln_factorials = ln_factorials(60);
+        // At least one of four copies in an opening hand of 60 cards.
+        assert_near(at_least(&ln_factorials, 60, 4, 7, 1), 0.3995);
+        assert_near(at_least(&ln_factorials, 60, 0, 7, 1), 0.0);
+        assert_near(at_least(&ln_factorials, 60, 60, 7, 7), 1.0);
+        assert_near(at_least(&ln_factorials, 60, 24, 7, 0), 1.0);
+    }
+
+    // Karsten's table of sources for casting a spell on curve 90% of the time in 60 cards asks for
+    // 14 for a {C} one-drop, 13 for a {1}{C} two-drop, 20 for {C}{C} on turn 2 and 18 for
+    // {1}{C}{C} on turn 3. His numbers also count mulligans and only hands with enough lands, so
+    // the plain odds of seeing the sources on the play come out lower.
+    #[test]
+    fn karsten_table_sources() {
+        let ln_factorials = ln_factorials(60);
+        assert_near(at_least(&ln_factorials, 60, 14, 7, 1), 0.8614);
+        assert_near(at_least(&ln_factorials, 60, 13, 8, 1), 0.8771);
+        assert_near(at_least(&ln_factorials, 60, 20, 8, 2), 0.8242);
+        assert_near(at_least(&ln_factorials, 60, 18, 9, 2), 0.8261);
+        // One more card seen on the draw.
+        assert_near(at_least(&ln_factorials, 60, 14, 8, 1), 0.8980);
+    }
+
+    static CARDS: &str = r#"[
+        {
+            "id": "1",
+            "scryfall_uri": "https://scryfall.com/card/lea/299",
+            "cmc": 0.0,
+            "color_identity": ["W", "U"],
+            "name": "Tundra",
+            "type_line": "Land — Plains Island",
+            "oracle_text": "({T}: Add {W} or {U}.)",
+            "produced_mana": ["W", "U"],
+            "set_type": "core"
+        },
+        {
+            "id": "2",
+            "scryfall_uri": "https://scryfall.com/card/lea/286",
+            "cmc": 0.0,
+            "color_identity": ["W"],
+            "name": "Plains",
+            "type_line": "Basic Land — Plains",
+            "oracle_text": "({T}: Add {W}.)",
+            "produced_mana": ["W"],
+            "set_type": "core"
+        },
+        {
+            "id": "3",
+            "scryfall_uri": "https://scryfall.com/card/lea/290",
+            "cmc": 0.0,
+            "color_identity": ["U"],
+            "name": "Island",
+            "type_line": "Basic Land — Island",
+            "oracle_text": "({T}: Add {U}.)",
+            "produced_mana": ["U"],
+            "set_type": "core"
+        },
+        {
+            "id": "4",
+            "scryfall_uri": "https://scryfall.com/card/ths/4",
+            "cmc": 3.0,
+            "mana_cost": "{1}{W}{W}",
+            "color_identity": ["W"],
+            "name": "Brimaz, King of Oreskos",
+            "type_line": "Legendary Creature — Cat Soldier",
+            "set_type": "expansion"
+        },
+        {
+            "id": "5",
+            "scryfall_uri": "https://scryfall.com/card/m12/63",
+            "cmc": 2.0,
+            "mana_cost": "{1}{U}",
+            "color_identity": ["U"],
+            "name": "Mana Leak",
+            "type_line": "Instant",
+            "set_type": "core"
+        },
+        {
+            "id": "6",
+            "scryfall_uri": "https://scryfall.com/card/atq/59",
+            "cmc": 0.0,
+            "mana_cost": "{0}",
+            "color_identity": [],
+            "name": "Ornithopter",
+            "type_line": "Artifact Creature — Thopter",
+            "set_type": "expansion"
+        }
+    ]"#;
+
+    #[test]
+    fn split_basics_between_two_colors() {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let card_tags: CardTags = toml::from_str("").unwrap();
+        let cards: CardList = serde_json::from_str(CARDS).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        let deck = Decklist::parse(
+            "4 Tundra\n10 Plains\n10 Island\n4 Brimaz, King of Oreskos\n4 Mana Leak\n28 Ornithopter",
+        )
+        .resolve(&carddb, &cards);
+        assert_eq!(deck.len(), 60);
+
+        let rows = optimize_basics(&deck, deck.basic_land_count(), false);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.color,
+                    row.hardest,
+                    row.nonbasic_sources,
+                    row.current_basics,
+                    row.basics,
+                )
+            })
+            .collect();
+        // {1}{W}{W} on turn 3 needs more sources than {1}{U} on turn 2, so the even split moves
+        // toward Plains.
+        assert_eq!(
+            summary,
+            [
+                (Color::White, ("Brimaz, King of Oreskos", 2, 3), 4, 10, 13),
+                (Color::Blue, ("Mana Leak", 1, 2), 4, 10, 7),
+            ]
+        );
+        assert_near(rows[0].current_castability, 0.6784);
+        assert_near(rows[0].castability, 0.7951);
+        assert_near(rows[1].current_castability, 0.8980);
+        assert_near(rows[1].castability, 0.8237);
+    }
+}
@@ -65,7 +65,67 @@ pub enum Colors {
     Domain,
 }
 
+/// A kind of mana a card can produce: colorless mana, one or more colors, one mana of any color,
+/// of a color in the commander's color identity, or snow mana.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub enum Mana {
+    Colorless,
+    Colors(Colors),
+    AnyColor,
+    CommanderColor,
+    Snow,
+}
+
+/// A symbol in a mana pool tag's `mana` list: a color, or `C`, `S`, `any` or `commander`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ManaSymbol {
+    Color(Color),
+    Other(OtherManaSymbol),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OtherManaSymbol {
+    #[serde(rename = "C")]
+    Colorless,
+    #[serde(rename = "S")]
+    Snow,
+    #[serde(rename = "any")]
+    AnyColor,
+    #[serde(rename = "commander")]
+    CommanderColor,
+}
+
 impl Color {
+    pub const ALL: [Color; 5] = [
+        Color::White,
+        Color::Blue,
+        Color::Black,
+        Color::Red,
+        Color::Green,
+    ];
+
+    pub fn from_symbol(symbol: &str) -> Option<Color> {
+        match symbol {
+            "W" => Some(Color::White),
+            "U" => Some(Color::Blue),
+            "B" => Some(Color::Black),
+            "R" => Some(Color::Red),
+            "G" => Some(Color::Green),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Color::White => "W",
+            Color::Blue => "U",
+            Color::Black => "B",
+            Color::Red => "R",
+            Color::Green => "G",
+        }
+    }
+
     pub fn mana_symbol(self) -> &'static str {
         match self {
             Color::White => "<span class=\"mana sw\"></span>",
@@ -88,6 +148,19 @@ impl Color {
 }
 
 impl DualColor {
+    pub const ALL: [DualColor; 10] = [
+        DualColor::Azorius,
+        DualColor::Dimir,
+        DualColor::Rakdos,
+        DualColor::Gruul,
+        DualColor::Selesnya,
+        DualColor::Orzhov,
+        DualColor::Boros,
+        DualColor::Izzet,
+        DualColor::Simic,
+        DualColor::Golgari,
+    ];
+
     pub fn name(self) -> &'static str {
         use DualColor::*;
         match self {
@@ -106,6 +179,19 @@ impl DualColor {
 }
 
 impl TriColor {
+    pub const ALL: [TriColor; 10] = [
+        TriColor::Bant,
+        TriColor::Esper,
+        TriColor::Grixis,
+        TriColor::Jund,
+        TriColor::Naya,
+        TriColor::Abzan,
+        TriColor::Jeskai,
+        TriColor::Sultai,
+        TriColor::Mardu,
+        TriColor::Temur,
+    ];
+
     pub fn name(self) -> &'static str {
         use TriColor::*;
         match self {
@@ -124,6 +210,14 @@ impl TriColor {
 }
 
 impl QuadColor {
+    pub const ALL: [QuadColor; 5] = [
+        QuadColor::Artifice,
+        QuadColor::Chaos,
+        QuadColor::Aggression,
+        QuadColor::Altruism,
+        QuadColor::Growth,
+    ];
+
     pub fn name(self) -> &'static str {
         use QuadColor::*;
         match self {
@@ -137,6 +231,17 @@ impl QuadColor {
 }
 
 impl Colors {
+    /// Every combination of colors, from colorless to all five.
+    pub fn all() -> Vec<Colors> {
+        let mut all = vec![Colors::Colorless];
+        all.extend(Color::ALL.map(Colors::Mono));
+        all.extend(DualColor::ALL.map(Colors::Dual));
+        all.extend(TriColor::ALL.map(Colors::Tri));
+        all.extend(QuadColor::ALL.map(Colors::Quad));
+        all.push(Colors::Domain);
+        all
+    }
+
     pub fn from_vec(mut colors: Vec<Color>) -> Self {
         use Color::*;
         use Colors::*;
@@ -229,6 +334,23 @@ impl Colors {
         }
     }
 
+    pub fn contains(self, color: Color) -> bool {
+        self.into_vec().contains(&color)
+    }
+
+    pub fn is_subset(self, other: Colors) -> bool {
+        self.into_vec().into_iter().all(|c| other.contains(c))
+    }
+
+    pub fn is_superset(self, other: Colors) -> bool {
+        other.is_subset(self)
+    }
+
+    /// The name of the combination's page, e.g. `azorius` for `colors-azorius.html`.
+    pub fn slug(self) -> String {
+        self.name().to_lowercase()
+    }
+
     pub fn name(self) -> &'static str {
         use Colors::*;
         match self {
@@ -242,6 +364,67 @@ impl Colors {
     }
 }
 
+impl Mana {
+    /// Reads a `mana` list, where an empty list is colorless mana. Colors can be combined with
+    /// each other but not with the other symbols.
+    pub fn from_symbols(symbols: &[ManaSymbol]) -> Result<Mana, String> {
+        let colors: Vec<_> = symbols
+            .iter()
+            .filter_map(|symbol| match symbol {
+                ManaSymbol::Color(color) => Some(*color),
+                ManaSymbol::Other(_) => None,
+            })
+            .collect();
+        match symbols {
+            [] | [ManaSymbol::Other(OtherManaSymbol::Colorless)] => Ok(Mana::Colorless),
+            [ManaSymbol::Other(OtherManaSymbol::Snow)] => Ok(Mana::Snow),
+            [ManaSymbol::Other(OtherManaSymbol::AnyColor)] => Ok(Mana::AnyColor),
+            [ManaSymbol::Other(OtherManaSymbol::CommanderColor)] => Ok(Mana::CommanderColor),
+            _ if colors.len() == symbols.len() => Ok(Mana::from(Colors::from_vec(colors))),
+            _ => Err("C, S, any and commander can't be combined with other mana".to_owned()),
+        }
+    }
+
+    /// Whether the mana can be spent as mana of a color. Mana of a color in the commander's color
+    /// identity is assumed to cover any color a deck needs.
+    pub fn provides(self, color: Color) -> bool {
+        match self {
+            Mana::Colors(colors) => colors.contains(color),
+            Mana::AnyColor | Mana::CommanderColor => true,
+            Mana::Colorless | Mana::Snow => false,
+        }
+    }
+
+    pub fn mana_symbols(self) -> Cow<'static, str> {
+        match self {
+            Mana::Colorless => Cow::Borrowed("<span class=\"mana sc\"></span>"),
+            Mana::Colors(colors) => colors.mana_symbols(),
+            Mana::AnyColor => Cow::Borrowed("<span class=\"mana sany\"></span>"),
+            Mana::CommanderColor => Cow::Borrowed("<span class=\"mana scommander\"></span>"),
+            Mana::Snow => Cow::Borrowed("<span class=\"mana ss\"></span>"),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mana::Colorless => "Colorless",
+            Mana::Colors(colors) => colors.name(),
+            Mana::AnyColor => "Any Color",
+            Mana::CommanderColor => "Commander Color",
+            Mana::Snow => "Snow",
+        }
+    }
+}
+
+impl From<Colors> for Mana {
+    fn from(colors: Colors) -> Mana {
+        match colors {
+            Colors::Colorless => Mana::Colorless,
+            colors => Mana::Colors(colors),
+        }
+    }
+}
+
 impl Display for Color {
     fn fmt(&self, fmt: &mut Formatter) -> std::fmt::Result {
         write!(fmt, "{} {}", self.mana_symbol(), self.name())
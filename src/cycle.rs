@@ -0,0 +1,221 @@
+use crate::{
+    card::{TaggedCard, TaggedCardDb},
+    color::Colors,
+    tags::TagRef,
+};
+
+/// The cards of a cycle tag sorted into the color combinations the cycle should cover.
+#[derive(Debug)]
+pub struct CycleReport<'d, 'a> {
+    slots: Vec<CycleSlot<'d, 'a>>,
+    /// Cards whose mana doesn't match any combination of the cycle.
+    others: Vec<&'d TaggedCard<'a>>,
+}
+
+#[derive(Debug)]
+pub struct CycleSlot<'d, 'a> {
+    colors: Colors,
+    cards: Vec<&'d TaggedCard<'a>>,
+}
+
+impl<'d, 'a> CycleReport<'d, 'a> {
+    /// Sorts a tag's cards by the colors of mana they produce, or returns `None` if the tag isn't
+    /// marked as a cycle.
+    pub fn new(tag: &TagRef<'a>, carddb: &'d TaggedCardDb<'a>) -> Option<CycleReport<'d, 'a>> {
+        let mut slots: Vec<_> = tag
+            .cycle()?
+            .combinations()
+            .into_iter()
+            .map(|colors| CycleSlot {
+                colors,
+                cards: Vec::new(),
+            })
+            .collect();
+        let mut cards: Vec<_> = carddb
+            .tag_index()
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| carddb.card_index().get(id))
+            .collect();
+        cards.sort_unstable_by_key(|card| &card.card().name);
+
+        let mut others = Vec::new();
+        for card in cards {
+            let colors = Colors::from_vec(card.card().produced_colors());
+            match slots.iter_mut().find(|slot| slot.colors == colors) {
+                Some(slot) => slot.cards.push(card),
+                None => others.push(card),
+            }
+        }
+        Some(CycleReport { slots, others })
+    }
+
+    pub fn slots(&self) -> &[CycleSlot<'d, 'a>] {
+        &self.slots
+    }
+
+    pub fn others(&self) -> &[&'d TaggedCard<'a>] {
+        &self.others
+    }
+
+    pub fn missing(&self) -> impl Iterator<Item = Colors> + '_ {
+        self.slots
+            .iter()
+            .filter(|slot| slot.is_missing())
+            .map(|slot| slot.colors)
+    }
+
+    pub fn duplicates(&self) -> impl Iterator<Item = &CycleSlot<'d, 'a>> {
+        self.slots.iter().filter(|slot| slot.is_duplicate())
+    }
+}
+
+impl<'d, 'a> CycleSlot<'d, 'a> {
+    pub fn colors(&self) -> Colors {
+        self.colors
+    }
+
+    pub fn cards(&self) -> &[&'d TaggedCard<'a>] {
+        &self.cards
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.cards.is_empty()
+    }
+
+    pub fn is_duplicate(&self) -> bool {
+        self.cards.len() > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::DualColor,
+        scryfall::CardList,
+        tags::{CardTags, TagIndex},
+    };
+    use std::path::Path;
+
+    static FAST_LANDS: [(&str, &[&str]); 10] = [
+        ("Seachrome Coast", &["W", "U"]),
+        ("Darkslick Shores", &["U", "B"]),
+        ("Blackcleave Cliffs", &["B", "R"]),
+        ("Copperline Gorge", &["R", "G"]),
+        ("Razorverge Thicket", &["G", "W"]),
+        ("Concealed Courtyard", &["W", "B"]),
+        ("Inspiring Vantage", &["R", "W"]),
+        ("Spirebluff Canal", &["U", "R"]),
+        ("Botanical Sanctum", &["G", "U"]),
+        ("Blooming Marsh", &["B", "G"]),
+    ];
+
+    fn cards_json(lands: &[(&str, &[&str])]) -> String {
+        let cards: Vec<_> = lands
+            .iter()
+            .enumerate()
+            .map(|(id, (name, produced_mana))| {
+                let color_identity: Vec<_> =
+                    produced_mana.iter().filter(|&&mana| mana != "C").collect();
+                serde_json::json!({
+                    "id": id.to_string(),
+                    "scryfall_uri": format!("https://scryfall.com/card/tst/{}", id),
+                    "cmc": 0.0,
+                    "color_identity": color_identity,
+                    "name": name,
+                    "type_line": "Land",
+                    "produced_mana": produced_mana,
+                    "set_type": "expansion"
+                })
+            })
+            .collect();
+        serde_json::Value::from(cards).to_string()
+    }
+
+    fn fast_dual_tags(lands: &[(&str, &[&str])]) -> CardTags {
+        let config: String = lands
+            .iter()
+            .map(|(name, _)| format!("\"{}\" = [\"Fast Dual\"]\n", name))
+            .collect();
+        toml::from_str(&config).unwrap()
+    }
+
+    fn report(lands: &[(&str, &[&str])], check: impl FnOnce(CycleReport)) {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let card_tags = fast_dual_tags(lands);
+        let json = cards_json(lands);
+        let cards: CardList = serde_json::from_str(&json).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        check(CycleReport::new(&tag_index.get("Fast Dual").unwrap(), &carddb).unwrap());
+    }
+
+    fn names<'d>(cards: &[&'d TaggedCard]) -> Vec<&'d str> {
+        cards.iter().map(|card| &*card.card().name).collect()
+    }
+
+    #[test]
+    fn only_cycle_tags_have_reports() {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let card_tags = fast_dual_tags(&[]);
+        let cards: CardList = serde_json::from_str("[]").unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        assert!(CycleReport::new(&tag_index.get("Fetch").unwrap(), &carddb).is_none());
+    }
+
+    #[test]
+    fn complete_cycle() {
+        report(&FAST_LANDS, |report| {
+            assert_eq!(report.slots().len(), 10);
+            for (slot, colors) in report.slots().iter().zip(DualColor::ALL) {
+                assert_eq!(slot.colors(), Colors::Dual(colors));
+                assert_eq!(slot.cards().len(), 1);
+            }
+            assert_eq!(names(report.slots()[0].cards()), ["Seachrome Coast"]);
+            assert_eq!(report.missing().count(), 0);
+            assert_eq!(report.duplicates().count(), 0);
+            assert!(report.others().is_empty());
+        });
+    }
+
+    #[test]
+    fn cycle_missing_a_guild() {
+        report(&FAST_LANDS[..9], |report| {
+            assert_eq!(
+                report.missing().collect::<Vec<_>>(),
+                [Colors::Dual(DualColor::Golgari)]
+            );
+            assert_eq!(report.duplicates().count(), 0);
+            assert!(report.others().is_empty());
+        });
+    }
+
+    #[test]
+    fn cards_outside_the_cycle_colors() {
+        let mut lands = FAST_LANDS.to_vec();
+        lands.push(("Mana Confluence", &["W", "U", "B", "R", "G"]));
+        // Lands without mana of their own aren't colorless duals either.
+        lands.push(("Polluted Delta", &[]));
+        lands.push(("Wastes", &["C"]));
+        lands.push(("Sea of Clouds", &["W", "U"]));
+        report(&lands, |report| {
+            assert_eq!(
+                names(report.others()),
+                ["Mana Confluence", "Polluted Delta", "Wastes"]
+            );
+            let duplicates: Vec<_> = report.duplicates().collect();
+            assert_eq!(duplicates.len(), 1);
+            assert_eq!(duplicates[0].colors(), Colors::Dual(DualColor::Azorius));
+            assert_eq!(
+                names(duplicates[0].cards()),
+                ["Sea of Clouds", "Seachrome Coast"]
+            );
+            assert_eq!(report.missing().count(), 0);
+        });
+    }
+}
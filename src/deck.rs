@@ -0,0 +1,439 @@
+use crate::{
+    card::{CardTraits, CardType, TaggedCard, TaggedCardDb},
+    color::Color,
+    scryfall::{Card, CardList},
+};
+use anyhow::Context;
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+use std::{collections::BTreeSet, path::Path};
+
+lazy_static! {
+    static ref ENTRY_REGEX: Regex =
+        Regex::new(r"^(?:(\d+)x?\s+)?(.+?)(?:\s+\([A-Za-z0-9]+\)(?:\s+\S+)?)?$").unwrap();
+}
+
+static CHEAP_MANA_TAGS: [&str; 4] = ["Ramp", "Draw", "Dork", "Rock"];
+const CHEAP_MANA_VALUE: f32 = 2.0;
+
+static SECTION_HEADERS: [&str; 4] = ["deck", "main", "mainboard", "commander"];
+// Sections whose cards aren't part of the main deck, up to the next main deck header. MTG Arena
+// exports start with an `About` section naming the deck and put `Companion` before `Deck`.
+static SKIPPED_HEADERS: [&str; 5] = [
+    "about",
+    "companion",
+    "sideboard",
+    "maybeboard",
+    "considering",
+];
+
+/// A list of card names and counts, in the plain text format most deck builders export.
+#[derive(Debug)]
+pub struct Decklist {
+    entries: Vec<(u32, String)>,
+}
+
+/// A decklist whose cards have been looked up in the tagged card database.
+#[derive(Debug)]
+pub struct Deck<'d, 'a> {
+    cards: Vec<DeckCard<'d, 'a>>,
+}
+
+/// A recommended number of lands for a deck, from Frank Karsten's regression over its average mana
+/// value and its number of cheap card draw and mana ramp spells.
+#[derive(Debug)]
+pub struct LandAdvice<'d> {
+    pub formula: &'static LandFormula,
+    pub spells: u32,
+    pub average_mana_value: f64,
+    /// Spells with mana value 2 or less tagged with any of the cheap mana tags, with their count
+    /// and matching tags.
+    pub cheap_spells: Vec<(&'d str, u32, Vec<&'static str>)>,
+    pub recommended: f64,
+}
+
+/// The lands in a deck that a fetch or tutor card can find, and the colors they produce.
+#[derive(Debug)]
+pub struct FetchAccess<'d> {
+    pub name: &'d str,
+    pub count: u32,
+    pub colors: Vec<Color>,
+    pub lands: Vec<&'d str>,
+}
+
+#[derive(Debug)]
+pub struct LandFormula {
+    pub deck_size: u32,
+    base: f64,
+    per_mana_value: f64,
+    pub per_cheap_spell: f64,
+    /// Whether the formula is scaled from another deck size rather than published for this one.
+    pub extrapolated: bool,
+}
+
+// Karsten published formulas for 60- and 100-card decks. The 40-card formula is not his: it's the
+// 60-card one scaled by 2/3 to the smaller deck.
+static LAND_FORMULAS: [LandFormula; 3] = [
+    LandFormula {
+        deck_size: 40,
+        base: 13.06,
+        per_mana_value: 1.27,
+        per_cheap_spell: 0.19,
+        extrapolated: true,
+    },
+    LandFormula {
+        deck_size: 60,
+        base: 19.59,
+        per_mana_value: 1.90,
+        per_cheap_spell: 0.28,
+        extrapolated: false,
+    },
+    LandFormula {
+        deck_size: 100,
+        base: 31.42,
+        per_mana_value: 3.13,
+        per_cheap_spell: 0.28,
+        extrapolated: false,
+    },
+];
+
+#[derive(Debug)]
+pub struct DeckCard<'d, 'a> {
+    count: u32,
+    card: Option<&'d Card<'d>>,
+    tagged: Option<&'d TaggedCard<'a>>,
+    /// The types and traits of a card that was found but isn't tagged.
+    untagged: Option<(BTreeSet<CardType>, CardTraits)>,
+}
+
+impl Decklist {
+    pub fn load(path: &Path) -> anyhow::Result<Decklist> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read decklist {}", path.display()))?;
+        Ok(Decklist::parse(&text))
+    }
+
+    /// Parses one `4 Card Name` (or `4x Card Name`, or just `Card Name`) entry per line, skipping
+    /// comments, `SB:` lines and sections such as the sideboard or companion until the next main
+    /// deck header. Set codes and collector numbers after the name are ignored.
+    pub fn parse(text: &str) -> Decklist {
+        let mut entries = Vec::new();
+        let mut skipping = false;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let header = line.trim_end_matches(':').to_lowercase();
+            if SKIPPED_HEADERS.contains(&header.as_str()) {
+                skipping = true;
+                continue;
+            }
+            if SECTION_HEADERS.contains(&header.as_str()) {
+                skipping = false;
+                continue;
+            }
+            if skipping || line.starts_with("SB:") {
+                continue;
+            }
+            if let Some(caps) = ENTRY_REGEX.captures(line) {
+                let count = caps
+                    .get(1)
+                    .and_then(|count| count.as_str().parse().ok())
+                    .unwrap_or(1);
+                entries.push((count, caps[2].to_owned()));
+            }
+        }
+        Decklist { entries }
+    }
+
+    /// Looks up each card by name, or by the name of its front face, among the tagged cards and
+    /// then all cards. Cards that can't be found are treated as spells with no cost.
+    pub fn resolve<'d, 'a>(
+        &self,
+        carddb: &'d TaggedCardDb<'a>,
+        cards: &'d CardList<'d>,
+    ) -> Deck<'d, 'a> {
+        let cards = self
+            .entries
+            .iter()
+            .map(|(count, name)| {
+                let tagged = carddb
+                    .find_by_name(name)
+                    .or_else(|| carddb.cards().find(|card| has_name(card.card(), name)));
+                let card = match tagged {
+                    Some(tagged) => Some(tagged.card()),
+                    None => cards.cards().iter().find(|card| has_name(card, name)),
+                };
+                if card.is_none() {
+                    warn!("card \"{}\" was not found; counting it as a spell", name);
+                }
+                DeckCard {
+                    count: *count,
+                    card,
+                    tagged,
+                    untagged: card
+                        .filter(|_| tagged.is_none())
+                        .map(|card| (CardType::from_card(card), CardTraits::from_card(card))),
+                }
+            })
+            .collect();
+        Deck { cards }
+    }
+}
+
+impl<'d, 'a> Deck<'d, 'a> {
+    pub fn cards(&self) -> &[DeckCard<'d, 'a>] {
+        &self.cards
+    }
+
+    pub fn len(&self) -> u32 {
+        self.cards.iter().map(|card| card.count).sum()
+    }
+
+    pub fn basic_land_count(&self) -> u32 {
+        self.cards
+            .iter()
+            .filter(|card| card.is_basic_land())
+            .map(|card| card.count)
+            .sum()
+    }
+
+    /// Recommends a land count using the formula for the closest of 40-, 60- and 100-card decks.
+    pub fn land_advice(&self) -> LandAdvice<'d> {
+        let formula = LAND_FORMULAS
+            .iter()
+            .min_by_key(|formula| formula.deck_size.abs_diff(self.len()))
+            .unwrap();
+        let spells: Vec<_> = self
+            .cards
+            .iter()
+            .filter(|deck_card| !deck_card.is_land())
+            .filter_map(|deck_card| Some((deck_card.card?, deck_card)))
+            .collect();
+        let count: u32 = spells.iter().map(|(_, deck_card)| deck_card.count).sum();
+        let total_mana_value: f64 = spells
+            .iter()
+            .map(|(card, deck_card)| card.cmc as f64 * deck_card.count as f64)
+            .sum();
+        let average_mana_value = total_mana_value / count.max(1) as f64;
+
+        let cheap_spells: Vec<_> = spells
+            .iter()
+            .filter(|(card, _)| card.cmc <= CHEAP_MANA_VALUE)
+            .filter_map(|(card, deck_card)| {
+                let tags = deck_card.tagged?.tags();
+                let cheap_tags: Vec<_> = CHEAP_MANA_TAGS
+                    .iter()
+                    .copied()
+                    .filter(|name| tags.iter().any(|tag| tag.name() == *name))
+                    .collect();
+                Some((card.name.as_ref(), deck_card.count, cheap_tags))
+                    .filter(|(_, _, tags)| !tags.is_empty())
+            })
+            .collect();
+        let cheap_count: u32 = cheap_spells.iter().map(|(_, count, _)| count).sum();
+
+        LandAdvice {
+            formula,
+            spells: count,
+            average_mana_value,
+            recommended: formula.base + formula.per_mana_value * average_mana_value
+                - formula.per_cheap_spell * cheap_count as f64,
+            cheap_spells,
+        }
+    }
+
+    /// For each card in the deck that searches for lands, the lands of the deck it can find.
+    pub fn fetch_access(&self) -> Vec<FetchAccess<'d>> {
+        self.cards
+            .iter()
+            .filter_map(|deck_card| {
+                let fetcher = deck_card.tagged.filter(|card| !card.fetches().is_empty())?;
+                let lands: Vec<_> = self
+                    .cards
+                    .iter()
+                    .filter(|land| land.is_land())
+                    .filter(|land| {
+                        land.traits().is_some_and(|traits| {
+                            fetcher
+                                .fetches()
+                                .iter()
+                                .any(|filter| filter.matches(traits.type_line()))
+                        })
+                    })
+                    .filter_map(|land| land.card)
+                    .collect();
+                let mut colors: Vec<_> = lands
+                    .iter()
+                    .flat_map(|land| land.produced_colors())
+                    .collect();
+                colors.sort_unstable();
+                colors.dedup();
+                Some(FetchAccess {
+                    name: fetcher.card().name.as_ref(),
+                    count: deck_card.count,
+                    colors,
+                    lands: lands.iter().map(|land| land.name.as_ref()).collect(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn land_count(&self) -> u32 {
+        self.cards
+            .iter()
+            .filter(|card| card.is_land())
+            .map(|card| card.count)
+            .sum()
+    }
+}
+
+impl<'d, 'a> DeckCard<'d, 'a> {
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn card(&self) -> Option<&'d Card<'d>> {
+        self.card
+    }
+
+    pub fn tagged(&self) -> Option<&'d TaggedCard<'a>> {
+        self.tagged
+    }
+
+    pub fn types(&self) -> Option<&BTreeSet<CardType>> {
+        self.tagged
+            .map(TaggedCard::types)
+            .or(self.untagged.as_ref().map(|(types, _)| types))
+    }
+
+    pub fn traits(&self) -> Option<&CardTraits> {
+        self.tagged
+            .map(TaggedCard::traits)
+            .or(self.untagged.as_ref().map(|(_, traits)| traits))
+    }
+
+    /// Whether the card can be played as a land, including modal double-faced cards.
+    pub fn is_land(&self) -> bool {
+        self.types().is_some_and(|types| {
+            types.contains(&CardType::Land) || types.contains(&CardType::ModalLand)
+        })
+    }
+
+    pub fn is_basic_land(&self) -> bool {
+        self.traits()
+            .is_some_and(|traits| traits.type_line().starts_with("Basic"))
+    }
+}
+
+fn has_name(card: &Card, name: &str) -> bool {
+    card.name.eq_ignore_ascii_case(name)
+        || card
+            .name
+            .split(" // ")
+            .next()
+            .filter(|front| front.eq_ignore_ascii_case(name))
+            .is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        oracle::EntersTapped,
+        tags::{CardTags, TagIndex},
+    };
+
+    static CARDS: &str = r#"[
+        {
+            "id": "1",
+            "scryfall_uri": "https://scryfall.com/card/snc/256",
+            "cmc": 0.0,
+            "color_identity": ["W", "U", "B"],
+            "name": "Raffine's Tower",
+            "type_line": "Land — Plains Island Swamp",
+            "oracle_text": "({T}: Add {W}, {U}, or {B}.)\nRaffine's Tower enters the battlefield tapped.",
+            "produced_mana": ["W", "U", "B"],
+            "set_type": "expansion"
+        },
+        {
+            "id": "2",
+            "scryfall_uri": "https://scryfall.com/card/lea/64",
+            "cmc": 2.0,
+            "mana_cost": "{U}{U}",
+            "color_identity": ["U"],
+            "name": "Counterspell",
+            "type_line": "Instant",
+            "oracle_text": "Counter target spell.",
+            "set_type": "core"
+        }
+    ]"#;
+
+    static ARENA_EXPORT: &str = "About
+Name Lurrus Pioneer
+
+Companion
+1 Lurrus of the Dream-Den (IKO) 226
+
+Deck
+4 Fabled Passage (ELD) 244
+4 Godless Shrine (RNA) 248
+4 Cauldron Familiar (ELD) 81
+4 Witch's Oven (ELD) 237
+
+Sideboard
+1 Lurrus of the Dream-Den (IKO) 226
+2 Duress (M21) 96
+";
+
+    #[test]
+    fn parse_arena_export() {
+        let decklist = Decklist::parse(ARENA_EXPORT);
+        assert_eq!(
+            decklist.entries,
+            [
+                (4, "Fabled Passage".to_owned()),
+                (4, "Godless Shrine".to_owned()),
+                (4, "Cauldron Familiar".to_owned()),
+                (4, "Witch's Oven".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_plain_decklist() {
+        let decklist = Decklist::parse(
+            "// Mono blue\n4x Island\nCounterspell\nSB: 2 Negate\n\nSideboard:\n1 Annul\n",
+        );
+        assert_eq!(
+            decklist.entries,
+            [(4, "Island".to_owned()), (1, "Counterspell".to_owned())]
+        );
+    }
+
+    #[test]
+    fn untagged_lands_count_as_lands() {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let card_tags: CardTags = toml::from_str("").unwrap();
+        let cards: CardList = serde_json::from_str(CARDS).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        let deck = Decklist::parse("4 Raffine's Tower\n2 Counterspell").resolve(&carddb, &cards);
+
+        let tower = &deck.cards()[0];
+        assert!(tower.tagged().is_none());
+        assert!(tower.is_land());
+        assert!(!tower.is_basic_land());
+        assert_eq!(
+            tower.traits().unwrap().enters_tapped(),
+            &EntersTapped::Tapped
+        );
+        assert_eq!(deck.land_count(), 4);
+
+        let advice = deck.land_advice();
+        assert_eq!(advice.spells, 2);
+        assert_eq!(advice.average_mana_value, 2.0);
+    }
+}
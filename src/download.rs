@@ -0,0 +1,209 @@
+use log::{debug, info, warn};
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Client, Response, StatusCode,
+};
+use serde::de::DeserializeOwned;
+use std::{future::Future, time::Duration};
+use tokio::time::{self, error::Elapsed};
+
+static USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+static ACCEPT: &str = "application/json;q=0.9,*/*;q=0.8";
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const PROGRESS_STEP: u64 = 10 * 1024 * 1024;
+
+#[derive(Debug)]
+pub struct Downloader {
+    client: Client,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl Downloader {
+    pub fn new(timeout: Duration, retries: u32) -> reqwest::Result<Downloader> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static(ACCEPT));
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .default_headers(headers)
+            .connect_timeout(timeout)
+            .build()?;
+        Ok(Downloader {
+            client,
+            timeout,
+            retries,
+        })
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, url: &str) -> anyhow::Result<T> {
+        self.with_retries(url, || async {
+            let response = self.send(url).await?;
+            Ok(time::timeout(self.timeout, response.json()).await??)
+        })
+        .await
+    }
+
+    pub async fn get_bytes(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        self.with_retries(url, || self.try_get_bytes(url)).await
+    }
+
+    async fn try_get_bytes(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        let mut response = self.send(url).await?;
+        let total = response.content_length();
+        let mut data = Vec::with_capacity(total.unwrap_or_default() as usize);
+        let mut next_progress = PROGRESS_STEP;
+        while let Some(chunk) = time::timeout(self.timeout, response.chunk()).await?? {
+            data.extend_from_slice(&chunk);
+            if data.len() as u64 >= next_progress {
+                next_progress += PROGRESS_STEP;
+                match total {
+                    Some(total) => info!(
+                        "downloaded {} of {} MiB ({}%)",
+                        data.len() >> 20,
+                        total >> 20,
+                        data.len() as u64 * 100 / total.max(1)
+                    ),
+                    None => info!("downloaded {} MiB", data.len() >> 20),
+                }
+            }
+        }
+        debug!("downloaded {} bytes from {}", data.len(), url);
+        Ok(data)
+    }
+
+    async fn send(&self, url: &str) -> anyhow::Result<Response> {
+        let response = time::timeout(self.timeout, self.client.get(url).send()).await??;
+        Ok(response.error_for_status()?)
+    }
+
+    async fn with_retries<T, F, Fut>(&self, url: &str, mut request: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+        loop {
+            match request().await {
+                Err(err) if attempt < self.retries && is_retryable(&err) => {
+                    attempt += 1;
+                    warn!(
+                        "request to {} failed ({}), retrying in {:?} ({}/{})",
+                        url, err, backoff, attempt, self.retries
+                    );
+                    time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_retryable(err: &anyhow::Error) -> bool {
+    if err.is::<Elapsed>() {
+        return true;
+    }
+    match err.downcast_ref::<reqwest::Error>() {
+        Some(err) => match err.status() {
+            Some(status) => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            None => err.is_timeout() || err.is_connect() || err.is_request() || err.is_body(),
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    static BODY: &str = r#"{"ok":true}"#;
+
+    // Serves one request per connection with each status in turn, after its delay. Returns the
+    // URL to request and the number of requests received.
+    async fn serve(responses: Vec<(u16, Duration)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/data.json", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for (status, delay) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await.unwrap();
+                received.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    time::sleep(delay).await;
+                    let response = format!(
+                        "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        BODY.len(),
+                        BODY
+                    );
+                    // The client may have timed out and hung up already.
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (url, requests)
+    }
+
+    fn downloader(timeout_ms: u64, retries: u32) -> Downloader {
+        Downloader::new(Duration::from_millis(timeout_ms), retries).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let (url, requests) = serve(vec![(503, Duration::ZERO), (200, Duration::ZERO)]).await;
+        let data = downloader(1000, 2).get_bytes(&url).await.unwrap();
+        assert_eq!(data, BODY.as_bytes());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retries() {
+        let (url, requests) = serve(vec![(503, Duration::ZERO), (503, Duration::ZERO)]).await;
+        let err = downloader(1000, 1)
+            .get_json::<serde_json::Value>(&url)
+            .await
+            .unwrap_err();
+        let status = err
+            .downcast_ref::<reqwest::Error>()
+            .and_then(|err| err.status());
+        assert_eq!(status, Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (url, requests) = serve(vec![(404, Duration::ZERO)]).await;
+        assert!(downloader(1000, 2).get_bytes(&url).await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn times_out() {
+        let (url, requests) = serve(vec![(200, Duration::from_secs(5))]).await;
+        let err = downloader(100, 0).get_bytes(&url).await.unwrap_err();
+        assert!(err.is::<Elapsed>(), "{}", err);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts() {
+        let (url, requests) =
+            serve(vec![(200, Duration::from_secs(5)), (200, Duration::ZERO)]).await;
+        let value: serde_json::Value = downloader(100, 1).get_json(&url).await.unwrap();
+        assert_eq!(value, serde_json::json!({ "ok": true }));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,21 +1,38 @@
 mod card;
+mod castability;
 mod color;
+mod cycle;
+mod deck;
+mod download;
+mod mtgjson;
+mod oracle;
 mod scryfall;
+mod simulate;
+mod source;
+mod symbols;
 mod tags;
 mod templates;
 
 use crate::{
     card::{CardType, TaggedCardDb},
-    scryfall::{BulkDataInfo, CardList},
-    tags::{CardTags, TagDb, TagIndex},
+    castability::basic_land_type,
+    color::Colors,
+    cycle::CycleReport,
+    deck::Decklist,
+    download::Downloader,
+    scryfall::PrintingPolicy,
+    simulate::{ManaRequirement, Simulator, TURNS},
+    source::BulkData,
+    tags::{CardTags, CycleKind, SlugHistory, TagDb, TagIndex},
 };
 use chrono::prelude::*;
-use clap::{Arg, Command};
+use clap::{value_parser, Arg, ArgAction, Command};
 use fs_extra::dir::{self, CopyOptions};
+use itertools::Itertools;
 use log::{debug, info, warn};
-use std::{collections::HashSet, path::Path};
-
-static BULK_DATA_API_URL: &'static str = "https://api.scryfall.com/bulk-data/oracle-cards";
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{collections::HashSet, path::Path, time::Duration};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -27,19 +44,311 @@ async fn main() -> anyhow::Result<()> {
 
     let matches = Command::new("manabase")
         .arg(Arg::new("data").short('d').long("data").num_args(1))
+        .arg(
+            Arg::new("source")
+                .short('s')
+                .long("source")
+                .num_args(1)
+                .value_parser(source::SOURCE_NAMES)
+                .default_value("scryfall"),
+        )
+        .arg(Arg::new("api-url").long("api-url").num_args(1))
+        .arg(
+            Arg::new("bulk-data")
+                .long("bulk-data")
+                .num_args(1)
+                .value_parser(scryfall::BULK_DATA_TYPES)
+                .default_value("oracle-cards"),
+        )
+        .arg(
+            Arg::new("printing")
+                .long("printing")
+                .num_args(1)
+                .default_value("newest"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .num_args(1)
+                .value_parser(value_parser!(u64))
+                .default_value("60"),
+        )
+        .arg(
+            Arg::new("retries")
+                .long("retries")
+                .num_args(1)
+                .value_parser(value_parser!(u32))
+                .default_value("3"),
+        )
         .arg(Arg::new("output").index(1).default_value("target/www"))
+        .subcommand(
+            Command::new("fmt").about("Rewrite alt-names in card tag files to their tag names"),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Explain why a card has each of its tags")
+                .arg(Arg::new("card").required(true)),
+        )
+        .subcommand(
+            Command::new("deck")
+                .about("Simulate how often a deck has untapped mana on each turn")
+                .arg(Arg::new("decklist").required(true))
+                .arg(
+                    Arg::new("need")
+                        .long("need")
+                        .num_args(1)
+                        .action(ArgAction::Append)
+                        .help("Mana to have untapped, e.g. 1WW; defaults to each color"),
+                )
+                .arg(
+                    Arg::new("trials")
+                        .long("trials")
+                        .num_args(1)
+                        .value_parser(value_parser!(u32))
+                        .default_value("10000"),
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .num_args(1)
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("opponents")
+                        .long("opponents")
+                        .num_args(1)
+                        .value_parser(value_parser!(u32))
+                        .help("Defaults to 3 for 100-card decks and 1 otherwise"),
+                )
+                .arg(
+                    Arg::new("basics")
+                        .long("basics")
+                        .num_args(1)
+                        .value_parser(value_parser!(u32))
+                        .help("Number of basic lands to split; defaults to the deck's count"),
+                )
+                .arg(
+                    Arg::new("draw")
+                        .long("draw")
+                        .action(ArgAction::SetTrue)
+                        .help("Simulate being on the draw"),
+                ),
+        )
         .get_matches();
 
     info!("loading config files");
     let config_dir = &Path::new("config");
     let mut tag_index = TagIndex::load(&config_dir.join("tags.toml"))?;
     let card_tags = CardTags::load(&config_dir.join("card-tags.toml"))?;
-    tag_index.merge_tags(&card_tags);
+    if matches.subcommand_matches("fmt").is_some() {
+        let count = CardTags::rewrite_aliases(&config_dir.join("card-tags.toml"), &tag_index)?;
+        info!("rewrote {} alt-names", count);
+        return Ok(());
+    }
+    tag_index.merge_tags(&card_tags)?;
     let tagdb = TagDb::new(&tag_index);
 
+    let source = source::from_name(
+        matches.get_one::<String>("source").unwrap(),
+        matches.get_one::<String>("api-url").map(String::as_str),
+        matches.get_one::<String>("bulk-data").unwrap(),
+    )
+    .unwrap();
+    let bulk_data = if let Some(path) = matches.get_one::<String>("data") {
+        let path = Path::new(path);
+        info!(
+            "loading {} bulk card data from {}",
+            source.name(),
+            path.display()
+        );
+        BulkData::load(path)?
+    } else {
+        let downloader = Downloader::new(
+            Duration::from_secs(*matches.get_one::<u64>("timeout").unwrap()),
+            *matches.get_one::<u32>("retries").unwrap(),
+        )?;
+        source.fetch(&downloader).await?
+    };
+    let data_updated = bulk_data.updated_at;
+    let timestamp = Utc::now();
+    let printing_policy =
+        PrintingPolicy::from(matches.get_one::<String>("printing").unwrap().as_str());
+    let cards = source
+        .parse(&bulk_data.data)?
+        .select_printings(&printing_policy, &card_tags);
+    debug!("loaded {} cards", cards.cards().len());
+
+    info!("tagging cards");
+    let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+
+    if let Some(explain) = matches.subcommand_matches("explain") {
+        let name = explain.get_one::<String>("card").unwrap();
+        match carddb.find_by_name(name) {
+            Some(card) => {
+                println!("{}", card.card().name);
+                for tag in card.tags() {
+                    println!("  {} ({})", tag.name(), card.explain_tag(&tag));
+                }
+                for ability in card.mana_abilities() {
+                    println!("  mana ability: {}", ability.text());
+                    println!("    {}", ability);
+                }
+                println!("  enters {}", card.enters_tapped());
+                for filter in card.fetches() {
+                    println!("  fetches {}", filter);
+                }
+            }
+            None => println!("card \"{}\" is not tagged", name),
+        }
+        return Ok(());
+    }
+
+    if let Some(deck_matches) = matches.subcommand_matches("deck") {
+        let decklist = Decklist::load(Path::new(
+            deck_matches.get_one::<String>("decklist").unwrap(),
+        ))?;
+        let deck = decklist.resolve(&carddb, &cards);
+        println!("{} cards, {} lands", deck.len(), deck.land_count());
+
+        let advice = deck.land_advice();
+        println!(
+            "recommended lands: {:.1} for {}-card decks{}, from an average mana value of {:.2} over \
+             {} spells",
+            advice.recommended,
+            advice.formula.deck_size,
+            if advice.formula.extrapolated {
+                " (extrapolated from the 60-card formula)"
+            } else {
+                ""
+            },
+            advice.average_mana_value,
+            advice.spells
+        );
+        for (name, count, tags) in &advice.cheap_spells {
+            println!(
+                "  {:<32}{:>3}  -{:.2}  ({})",
+                name,
+                count,
+                advice.formula.per_cheap_spell * *count as f64,
+                tags.join(", ")
+            );
+        }
+
+        let fetches = deck.fetch_access();
+        if !fetches.is_empty() {
+            println!();
+            println!("fetches");
+            for fetch in &fetches {
+                println!(
+                    "  {:<32}{:>3}  {:<12}{}",
+                    fetch.name,
+                    fetch.count,
+                    fetch
+                        .colors
+                        .iter()
+                        .map(|color| format!("{{{}}}", color.symbol()))
+                        .join(""),
+                    if fetch.lands.is_empty() {
+                        "finds no lands in the deck".to_owned()
+                    } else {
+                        fetch.lands.join(", ")
+                    }
+                );
+            }
+        }
+
+        let on_the_draw = deck_matches.get_flag("draw");
+        let basics = deck_matches
+            .get_one::<u32>("basics")
+            .copied()
+            .unwrap_or_else(|| deck.basic_land_count());
+        let split = castability::optimize_basics(&deck, basics, on_the_draw);
+        if !split.is_empty() {
+            println!();
+            println!(
+                "recommended basics: {}",
+                split
+                    .iter()
+                    .map(|row| format!("{} {}", row.basics, basic_land_type(row.color)))
+                    .join(", ")
+            );
+            println!(
+                "{:<7}{:<32}{:>5}{:>6}{:>10}{:>14}{:>9}{:>20}",
+                "color",
+                "hardest spell",
+                "pips",
+                "turn",
+                "nonbasic",
+                "basics",
+                "sources",
+                "castable"
+            );
+            for row in &split {
+                let (name, pips, turn) = row.hardest;
+                println!(
+                    "{:<7}{:<32}{:>5}{:>6}{:>10}{:>14}{:>9}{:>20}",
+                    format!("{{{}}}", row.color.symbol()),
+                    name,
+                    pips,
+                    turn,
+                    row.nonbasic_sources,
+                    format!("{} (was {})", row.basics, row.current_basics),
+                    row.nonbasic_sources + row.basics,
+                    format!(
+                        "{:.1}% (was {:.1}%)",
+                        row.castability * 100.0,
+                        row.current_castability * 100.0
+                    ),
+                );
+            }
+        }
+
+        let opponents = deck_matches
+            .get_one::<u32>("opponents")
+            .copied()
+            .unwrap_or(if deck.len() >= 100 { 3 } else { 1 });
+        let simulator = Simulator::new(&deck, opponents, on_the_draw);
+        let requirements = match deck_matches.get_many::<String>("need") {
+            Some(needs) => needs
+                .map(|need| ManaRequirement::parse(need))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            None => simulator
+                .colors()
+                .into_iter()
+                .map(ManaRequirement::from_color)
+                .collect(),
+        };
+        let trials = *deck_matches.get_one::<u32>("trials").unwrap();
+        let seed = deck_matches
+            .get_one::<u64>("seed")
+            .copied()
+            .unwrap_or_else(rand::random);
+        println!();
+        println!("untapped mana by turn ({} trials, seed {})", trials, seed);
+        println!(
+            "{:<16}{}",
+            "",
+            (1..=TURNS)
+                .map(|turn| format!("{:>8}", format!("T{}", turn)))
+                .join("")
+        );
+        for requirement in &requirements {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let odds = simulator.run(requirement, trials, &mut rng);
+            println!(
+                "{:<16}{}",
+                requirement.to_string(),
+                odds.iter()
+                    .map(|odds| format!("{:>7.1}%", odds * 100.0))
+                    .join("")
+            );
+        }
+        return Ok(());
+    }
+
     let output_dir = Path::new(matches.get_one::<String>("output").unwrap());
 
-    std::fs::create_dir_all(&output_dir)?;
+    std::fs::create_dir_all(output_dir)?;
     let copy_opts = CopyOptions {
         overwrite: true,
         ..CopyOptions::new()
@@ -48,174 +357,125 @@ async fn main() -> anyhow::Result<()> {
     dir::copy("style", output_dir, &copy_opts)?;
     dir::copy("script", output_dir, &copy_opts)?;
 
-    let bulk_data: String;
-    let data_updated: DateTime<Utc>;
-    if let Some(path) = matches.get_one::<String>("data") {
-        let path = Path::new(path);
-        info!("loading Scryfall bulk card data from {}", path.display());
-        bulk_data = std::fs::read_to_string(path)?;
-        data_updated = std::fs::metadata(path)?.modified()?.into();
-    } else {
-        let bulk_data_info = reqwest::get(BULK_DATA_API_URL)
-            .await?
-            .json::<BulkDataInfo>()
-            .await?;
-
-        info!(
-            "loading Scryfall bulk card data from {}",
-            &bulk_data_info.download_uri
-        );
-        bulk_data = reqwest::get(&bulk_data_info.download_uri)
-            .await?
-            .text()
-            .await?;
-        data_updated = bulk_data_info.updated_at;
-    }
-    let timestamp = Utc::now();
-    let cards: CardList = serde_json::from_str(&bulk_data)?;
-    debug!("loaded {} cards", cards.cards().len());
-
-    info!("tagging cards");
-    let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
-
     info!("creating template pages");
-    templates::IndexPage::new(&tagdb, &carddb, timestamp.clone(), data_updated.clone())
-        .write_output(&output_dir)?;
+    templates::IndexPage::new(&tagdb, &carddb, timestamp, data_updated).write_output(output_dir)?;
     debug!("writing all cards page");
-    templates::AllCards::new(&carddb, timestamp.clone(), data_updated.clone())
-        .write_output(&output_dir)?;
+    templates::AllCards::new(&carddb, timestamp, data_updated).write_output(output_dir)?;
     debug!("writing all type card pages");
-    templates::TypeAllCards::new(
-        CardType::Land,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Artifact,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Creature,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Enchantment,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Instant,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Sorcery,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Planeswalker,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypeAllCards::new(
-        CardType::Battle,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
+    templates::TypeAllCards::new(CardType::Land, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::ModalLand, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Artifact, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Creature, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Enchantment, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Instant, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Sorcery, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Planeswalker, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypeAllCards::new(CardType::Battle, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
     debug!("writing card type pages");
+    templates::TypePage::new(CardType::Land, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
     templates::TypePage::new(
-        CardType::Land,
-        &tagdb,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypePage::new(
-        CardType::Artifact,
+        CardType::ModalLand,
         &tagdb,
         &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
+        timestamp,
+        data_updated,
     )
-    .write_output(&output_dir)?;
-    templates::TypePage::new(
-        CardType::Creature,
-        &tagdb,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
+    .write_output(output_dir)?;
+    templates::TypePage::new(CardType::Artifact, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypePage::new(CardType::Creature, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
     templates::TypePage::new(
         CardType::Enchantment,
         &tagdb,
         &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypePage::new(
-        CardType::Instant,
-        &tagdb,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypePage::new(
-        CardType::Sorcery,
-        &tagdb,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
+        timestamp,
+        data_updated,
     )
-    .write_output(&output_dir)?;
+    .write_output(output_dir)?;
+    templates::TypePage::new(CardType::Instant, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    templates::TypePage::new(CardType::Sorcery, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
     templates::TypePage::new(
         CardType::Planeswalker,
         &tagdb,
         &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
-    )
-    .write_output(&output_dir)?;
-    templates::TypePage::new(
-        CardType::Battle,
-        &tagdb,
-        &carddb,
-        timestamp.clone(),
-        data_updated.clone(),
+        timestamp,
+        data_updated,
     )
-    .write_output(&output_dir)?;
+    .write_output(output_dir)?;
+    templates::TypePage::new(CardType::Battle, &tagdb, &carddb, timestamp, data_updated)
+        .write_output(output_dir)?;
+    let history_file = config_dir.join("slug-history.toml");
+    let mut slug_history = SlugHistory::load(&history_file)?;
+    debug!("writing tag redirect pages");
+    for (slug, tag) in slug_history.redirects(&tag_index) {
+        templates::RedirectPage::new(slug, tag).write_output(output_dir)?;
+    }
+    if slug_history.update(&tag_index) {
+        info!("updating slug history");
+        slug_history.save(&history_file)?;
+    }
     debug!("writing tag pages");
     for (_, tag) in tag_index.iter() {
-        templates::TagPage::new(
-            tag,
-            &tag_index,
-            &carddb,
-            timestamp.clone(),
-            data_updated.clone(),
-        )
-        .write_output(&output_dir)?;
+        templates::TagPage::new(tag, &tag_index, &tagdb, &carddb, timestamp, data_updated)
+            .write_output(output_dir)?;
+    }
+
+    debug!("writing card pages");
+    for card in carddb.cards() {
+        templates::CardPage::new(card, &carddb, timestamp, data_updated)
+            .write_output(output_dir)?;
+    }
+
+    debug!("writing land cycle pages");
+    for cycle_kind in [CycleKind::Dual, CycleKind::Tri] {
+        templates::CycleMatrixPage::new(cycle_kind, &tag_index, &carddb, timestamp, data_updated)
+            .write_output(output_dir)?;
+    }
+    for colors in Colors::all() {
+        templates::ColorsPage::new(colors, &tag_index, &carddb, timestamp, data_updated)
+            .write_output(output_dir)?;
+    }
+
+    debug!("checking cycles");
+    for (name, tag) in tag_index.iter().sorted_by_key(|(name, _)| *name) {
+        if let Some(cycle) = CycleReport::new(&tag, &carddb) {
+            let missing: Vec<_> = cycle.missing().map(|colors| colors.name()).collect();
+            if !missing.is_empty() {
+                warn!("cycle \"{}\" is missing {}", name, missing.join(", "));
+            }
+            for slot in cycle.duplicates() {
+                warn!(
+                    "cycle \"{}\" has more than one {} card: {}",
+                    name,
+                    slot.colors().name(),
+                    slot.cards()
+                        .iter()
+                        .map(|card| format!("\"{}\"", card.card().name))
+                        .join(", ")
+                );
+            }
+            for card in cycle.others() {
+                warn!(
+                    "card \"{}\" in cycle \"{}\" produces {}",
+                    card.card().name,
+                    name,
+                    Colors::from_vec(card.card().produced_colors()).name()
+                );
+            }
+        }
     }
 
     debug!("checking for invalid cards");
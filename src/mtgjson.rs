@@ -0,0 +1,262 @@
+use crate::{
+    color::Color,
+    download::Downloader,
+    oracle::ManaAbility,
+    scryfall::{Card, CardFace, CardList, Format, Layout, Legality, SetType},
+    source::{read_data, BulkData, CardSource, FetchFuture},
+};
+use chrono::prelude::*;
+use log::info;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+static DEFAULT_API_URL: &str = "https://mtgjson.com/api/v5";
+static SCRYFALL_IMAGE_URL: &str = "https://api.scryfall.com/cards/named";
+static SCRYFALL_SEARCH_URL: &str = "https://scryfall.com/search";
+
+#[derive(Debug)]
+pub struct MtgJsonSource {
+    api_url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MetaFile {
+    data: Meta,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Meta {
+    date: NaiveDate,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AtomicCardsFile<'a> {
+    #[serde(borrow)]
+    data: BTreeMap<Cow<'a, str>, Vec<AtomicCard<'a>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AtomicCard<'a> {
+    #[serde(borrow)]
+    name: Cow<'a, str>,
+    #[serde(default, borrow)]
+    face_name: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    side: Option<Cow<'a, str>>,
+    #[serde(default, borrow, rename = "type")]
+    type_line: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    mana_cost: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    text: Option<Cow<'a, str>>,
+    #[serde(default)]
+    layout: Layout,
+    #[serde(default)]
+    mana_value: f32,
+    #[serde(default)]
+    color_identity: Vec<Color>,
+    #[serde(default)]
+    legalities: HashMap<Format, MtgJsonLegality>,
+    #[serde(default)]
+    is_funny: bool,
+    #[serde(default, borrow)]
+    first_printing: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    identifiers: Identifiers<'a>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identifiers<'a> {
+    #[serde(default, borrow)]
+    scryfall_oracle_id: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum MtgJsonLegality {
+    Legal,
+    Restricted,
+    Banned,
+    #[serde(other)]
+    NotLegal,
+}
+
+impl MtgJsonSource {
+    pub fn new(api_url: Option<&str>) -> MtgJsonSource {
+        MtgJsonSource {
+            api_url: api_url
+                .unwrap_or(DEFAULT_API_URL)
+                .trim_end_matches('/')
+                .to_owned(),
+        }
+    }
+}
+
+impl CardSource for MtgJsonSource {
+    fn name(&self) -> &'static str {
+        "MTGJSON"
+    }
+
+    fn fetch<'a>(&'a self, downloader: &'a Downloader) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let meta: MetaFile = downloader
+                .get_json(&format!("{}/Meta.json", self.api_url))
+                .await?;
+
+            let atomic_cards_url = format!("{}/AtomicCards.json", self.api_url);
+            info!("loading MTGJSON atomic card data from {}", atomic_cards_url);
+            let data = read_data(&downloader.get_bytes(&atomic_cards_url).await?[..])?;
+            Ok(BulkData {
+                data,
+                updated_at: Utc.from_utc_datetime(&meta.data.date.and_time(NaiveTime::MIN)),
+            })
+        })
+    }
+
+    fn parse<'a>(&self, data: &'a str) -> anyhow::Result<CardList<'a>> {
+        let atomic_cards: AtomicCardsFile<'a> = serde_json::from_str(data)?;
+        Ok(atomic_cards
+            .data
+            .into_values()
+            .filter_map(map_card)
+            .collect::<Vec<_>>()
+            .into())
+    }
+}
+
+fn map_card(mut faces: Vec<AtomicCard<'_>>) -> Option<Card<'_>> {
+    faces.sort_by(|a, b| a.side.cmp(&b.side));
+    let front = faces.first()?;
+    let name = front.name.clone();
+    let face_images = front.layout.has_face_images();
+
+    let legalities = Format::ALL
+        .iter()
+        .map(|format| {
+            let legality = match front.legalities.get(format) {
+                Some(MtgJsonLegality::Legal) => Legality::Legal,
+                Some(MtgJsonLegality::Restricted) => Legality::Restricted,
+                Some(MtgJsonLegality::Banned) => Legality::Banned,
+                Some(MtgJsonLegality::NotLegal) | None => Legality::NotLegal,
+            };
+            (*format, legality)
+        })
+        .collect();
+
+    let card_faces = if faces.len() > 1 {
+        Some(
+            faces
+                .iter()
+                .enumerate()
+                .map(|(i, face)| CardFace {
+                    image_uris: Some(image_uris(&name, i > 0)).filter(|_| face_images),
+                    name: face.face_name.clone().unwrap_or_else(|| face.name.clone()),
+                    mana_cost: Some(mana_cost(face)),
+                    type_line: face.type_line.clone(),
+                    oracle_text: face.text.clone(),
+                })
+                .collect(),
+        )
+    } else {
+        None
+    };
+    let type_line = itertools::join(faces.iter().flat_map(|face| face.type_line.iter()), " // ");
+
+    Some(Card {
+        id: front
+            .identifiers
+            .scryfall_oracle_id
+            .clone()
+            .unwrap_or_else(|| name.clone()),
+        scryfall_uri: Cow::Owned(
+            Url::parse_with_params(SCRYFALL_SEARCH_URL, &[("q", format!("!\"{}\"", name))])
+                .ok()?
+                .into(),
+        ),
+        card_faces,
+        cmc: front.mana_value,
+        mana_cost: if faces.len() > 1 {
+            None
+        } else {
+            Some(mana_cost(front))
+        },
+        color_identity: front.color_identity.clone(),
+        type_line: Some(Cow::Owned(type_line)),
+        image_uris: Some(image_uris(&name, false)).filter(|_| !face_images),
+        set_type: if front.is_funny {
+            SetType::Funny
+        } else {
+            SetType::Other
+        },
+        digital: false,
+        legalities: Some(legalities),
+        set: front
+            .first_printing
+            .as_ref()
+            .map(|set| Cow::Owned(set.to_lowercase())),
+        released_at: None,
+        layout: front.layout,
+        produced_mana: produced_mana(&faces),
+        oracle_text: if faces.len() > 1 {
+            None
+        } else {
+            front.text.clone()
+        },
+        name,
+    })
+}
+
+// MTGJSON doesn't list the mana a card produces, so read it from the card's mana abilities like
+// Scryfall does, with every color for abilities adding mana of any color.
+fn produced_mana<'a>(faces: &[AtomicCard<'_>]) -> Option<Vec<Cow<'a, str>>> {
+    let abilities: Vec<_> = faces
+        .iter()
+        .filter_map(|face| face.text.as_deref())
+        .flat_map(ManaAbility::parse_all)
+        .collect();
+    let mut colors: Vec<_> = abilities
+        .iter()
+        .flat_map(|ability| {
+            if ability.any_color() {
+                &Color::ALL[..]
+            } else {
+                ability.colors()
+            }
+        })
+        .copied()
+        .collect();
+    colors.sort_unstable();
+    colors.dedup();
+    let mut produced: Vec<_> = colors
+        .into_iter()
+        .map(|color| Cow::Borrowed(color.symbol()))
+        .collect();
+    if abilities.iter().any(ManaAbility::colorless) {
+        produced.push(Cow::Borrowed("C"));
+    }
+    Some(produced).filter(|produced| !produced.is_empty())
+}
+
+// MTGJSON leaves out the mana cost of cards without one, where Scryfall has an empty string.
+fn mana_cost<'a>(face: &AtomicCard<'a>) -> Cow<'a, str> {
+    face.mana_cost.clone().unwrap_or_default()
+}
+
+fn image_uris<'a>(name: &str, back: bool) -> HashMap<Cow<'a, str>, Cow<'a, str>> {
+    let mut params = vec![("exact", name), ("format", "image"), ("version", "normal")];
+    if back {
+        params.push(("face", "back"));
+    }
+    Url::parse_with_params(SCRYFALL_IMAGE_URL, &params)
+        .map(|url| {
+            let mut uris = HashMap::new();
+            uris.insert(Cow::Borrowed("normal"), Cow::Owned(url.into()));
+            uris
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,870 @@
+use crate::color::Color;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+static ANY_COLOR_PATTERNS: [&str; 5] = [
+    "any color",
+    "any one color",
+    "combination of colors",
+    "any type",
+    "chosen color",
+];
+
+lazy_static! {
+    static ref REMINDER_TEXT_REGEX: Regex = Regex::new(r"\s*\([^)]*\)").unwrap();
+    static ref ADD_CLAUSE_REGEX: Regex = Regex::new(r"\b[Aa]dd ([^.]+)").unwrap();
+    static ref MANA_SYMBOL_REGEX: Regex = Regex::new(r"\{([^}]+)\}").unwrap();
+    static ref MANA_COST_REGEX: Regex = Regex::new(r"^(\{[^}]+\})+$").unwrap();
+    static ref MANA_AMOUNT_REGEX: Regex = Regex::new(r"^(\w+) mana\b").unwrap();
+    static ref MANA_OPTION_SPLIT_REGEX: Regex = Regex::new(r",\s*(?:or\s+)?|\s+or\s+").unwrap();
+    static ref PAY_LIFE_REGEX: Regex = Regex::new(r"^[Pp]ay (\w+) life$").unwrap();
+    static ref SELF_DAMAGE_REGEX: Regex = Regex::new(r"deals (\w+) damage to you").unwrap();
+    static ref RESTRICTION_REGEX: Regex =
+        Regex::new(r"(Spend this mana only|This mana can't be spent)[^.]*").unwrap();
+    static ref ETB_PAY_LIFE_REGEX: Regex = Regex::new(
+        r"you may pay (\w+) life\. If you don't, [^.]* enters(?: the battlefield)? tapped"
+    )
+    .unwrap();
+    static ref ETB_REVEAL_REGEX: Regex = Regex::new(
+        r"you may reveal an? ([^.]+?) card from your hand\. If you don't, [^.]* enters(?: the battlefield)? tapped"
+    )
+    .unwrap();
+    static ref ETB_IF_LANDS_REGEX: Regex = Regex::new(
+        r"If you control (\w+) or more other lands, [^.]* enters(?: the battlefield)? tapped"
+    )
+    .unwrap();
+    static ref ETB_TAPPED_REGEX: Regex =
+        Regex::new(r"\benters(?: the battlefield)? tapped(?: unless ([^.]+))?\.").unwrap();
+    static ref AT_MOST_LANDS_REGEX: Regex =
+        Regex::new(r"^you control (\w+) or fewer other lands$").unwrap();
+    static ref AT_LEAST_LANDS_REGEX: Regex =
+        Regex::new(r"^you control (\w+) or more other lands$").unwrap();
+    static ref OPPONENTS_REGEX: Regex = Regex::new(r"^you have (\w+) or more opponents$").unwrap();
+    static ref CONTROL_REGEX: Regex = Regex::new(r"^you control (an? .+)$").unwrap();
+    static ref ALTERNATIVES_SPLIT_REGEX: Regex = Regex::new(r",?\s+or\s+|,\s+").unwrap();
+    static ref SEARCH_REGEX: Regex = Regex::new(
+        r"[Ss]earch (?:your|their) library for (?:up to )?(?:an?|one|two|three|four|five|X|any number of) (.+?) cards?(?: and an? (.+?) cards?)?( with a basic land type)?\b"
+    )
+    .unwrap();
+    static ref LANDCYCLING_REGEX: Regex =
+        Regex::new(r"^(Basic land|Land|Plains|Island|Swamp|Mountain|Forest|Desert)cycling\b")
+            .unwrap();
+}
+
+static BASIC_LAND_TYPES: [&str; 5] = ["Plains", "Island", "Swamp", "Mountain", "Forest"];
+
+/// An ability of a card that adds mana, parsed from its oracle text.
+#[derive(Debug, Clone)]
+pub struct ManaAbility {
+    text: String,
+    costs: Vec<ActivationCost>,
+    amount: ManaAmount,
+    colors: Vec<Color>,
+    colorless: bool,
+    any_color: bool,
+    commander_color: bool,
+    restriction: Option<String>,
+    self_damage: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManaAmount {
+    Fixed(u32),
+    Variable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActivationCost {
+    Tap,
+    Untap,
+    Mana(String),
+    PayLife(u32),
+    Sacrifice(String),
+    TapUntapped(String),
+    Discard(String),
+    RemoveCounters(String),
+    Other(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CostKind {
+    Tap,
+    Mana,
+    Life,
+    Sacrifice,
+    TapUntapped,
+    Discard,
+    RemoveCounters,
+}
+
+/// Under what condition a card enters the battlefield untapped, parsed from its oracle text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EntersTapped {
+    #[default]
+    Untapped,
+    Tapped,
+    UnlessLandType(Vec<String>),
+    UnlessAtMostLands(u32),
+    UnlessAtLeastLands(u32),
+    UnlessOpponents(u32),
+    UnlessPayLife(u32),
+    UnlessReveal(Vec<String>),
+    Unless(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntersTappedCondition {
+    Untapped,
+    Tapped,
+    UnlessLandType,
+    UnlessAtMostLands,
+    UnlessAtLeastLands,
+    UnlessOpponents,
+    UnlessPayLife,
+    UnlessReveal,
+    Unless,
+}
+
+/// Criteria for a tag rule matching cards by their enters-tapped condition, optionally with its
+/// count of land types, lands, opponents, life or revealed card types.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EntersTappedFilter {
+    Condition(EntersTappedCondition),
+    Rule {
+        condition: EntersTappedCondition,
+        #[serde(default)]
+        count: Option<u32>,
+    },
+}
+
+/// The lands a card can search its library for, parsed from its oracle text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchFilter {
+    basic: bool,
+    snow: bool,
+    /// Any of these subtypes, or any land if empty.
+    types: Vec<String>,
+}
+
+/// Criteria for a tag rule matching a card with any mana ability meeting all of them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ManaAbilityFilter {
+    #[serde(default)]
+    min_amount: Option<u32>,
+    #[serde(default)]
+    costs: Vec<CostKind>,
+    #[serde(default)]
+    restricted: Option<bool>,
+    #[serde(default)]
+    any_color: Option<bool>,
+}
+
+impl ManaAbility {
+    /// Parses every mana ability out of a card face's oracle text. Damage dealt to you whenever
+    /// the card becomes tapped counts towards each of its abilities that cost {T}.
+    pub fn parse_all(oracle_text: &str) -> Vec<ManaAbility> {
+        let lines: Vec<_> = oracle_text.lines().map(strip_reminder_text).collect();
+        let tap_damage = lines
+            .iter()
+            .filter(|line| line.contains("becomes tapped"))
+            .filter_map(|line| self_damage(line))
+            .sum::<u32>();
+        lines
+            .iter()
+            .filter_map(|line| ManaAbility::parse(line))
+            .map(|mut ability| {
+                if ability.costs.contains(&ActivationCost::Tap) {
+                    ability.self_damage += tap_damage;
+                }
+                ability
+            })
+            .collect()
+    }
+
+    fn parse(line: &str) -> Option<ManaAbility> {
+        let clause = ADD_CLAUSE_REGEX
+            .captures_iter(line)
+            .find(|caps| !line[..caps.get(0).unwrap().start()].ends_with("would "))?;
+        let add_start = clause.get(0).unwrap().start();
+        let produced = clause.get(1).unwrap().as_str();
+        if !produced.starts_with('{') && !produced.contains("mana") {
+            return None;
+        }
+
+        let costs = match line[..add_start].find(": ") {
+            Some(end) if !line[..end].contains('.') => {
+                line[..end].split(", ").map(ActivationCost::parse).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        let symbols: Vec<_> = MANA_SYMBOL_REGEX
+            .captures_iter(produced)
+            .map(|caps| caps.get(1).unwrap().as_str())
+            .collect();
+        let mut colors: Vec<_> = symbols
+            .iter()
+            .filter_map(|symbol| Color::from_symbol(symbol))
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        let variable = produced.contains("for each")
+            || produced.contains("equal to")
+            || produced.starts_with("an amount")
+            || produced.starts_with("that much");
+        let amount = match MANA_AMOUNT_REGEX.captures(produced) {
+            _ if variable => ManaAmount::Variable,
+            Some(caps) => parse_number(&caps[1]).map_or(ManaAmount::Variable, ManaAmount::Fixed),
+            None => ManaAmount::Fixed(
+                MANA_OPTION_SPLIT_REGEX
+                    .split(produced)
+                    .map(|option| MANA_SYMBOL_REGEX.find_iter(option).count() as u32)
+                    .max()
+                    .unwrap_or_default(),
+            ),
+        };
+
+        Some(ManaAbility {
+            text: line.to_owned(),
+            costs,
+            amount,
+            colors,
+            colorless: symbols.contains(&"C"),
+            any_color: ANY_COLOR_PATTERNS
+                .iter()
+                .any(|pattern| produced.contains(pattern)),
+            commander_color: produced.contains("commander's color identity"),
+            restriction: RESTRICTION_REGEX
+                .find(line)
+                .map(|restriction| restriction.as_str().to_owned()),
+            self_damage: self_damage(&line[add_start..]).unwrap_or_default(),
+        })
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn costs(&self) -> &[ActivationCost] {
+        &self.costs
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn colorless(&self) -> bool {
+        self.colorless
+    }
+
+    /// Whether the ability adds mana of a color chosen as it resolves, including a color in the
+    /// commander's color identity.
+    pub fn any_color(&self) -> bool {
+        self.any_color
+    }
+
+    pub fn commander_color(&self) -> bool {
+        self.commander_color
+    }
+
+    pub fn amount(&self) -> ManaAmount {
+        self.amount
+    }
+
+    /// Life paid or damage taken each time the ability is used.
+    pub fn life_cost(&self) -> u32 {
+        self.self_damage
+            + self
+                .costs
+                .iter()
+                .map(|cost| match cost {
+                    ActivationCost::PayLife(life) => *life,
+                    _ => 0,
+                })
+                .sum::<u32>()
+    }
+
+    fn has_cost(&self, kind: CostKind) -> bool {
+        match kind {
+            CostKind::Life => self.life_cost() > 0,
+            kind => self.costs.iter().any(|cost| cost.kind() == Some(kind)),
+        }
+    }
+}
+
+impl ActivationCost {
+    fn parse(cost: &str) -> ActivationCost {
+        let cost = cost.trim();
+        match cost {
+            "{T}" => ActivationCost::Tap,
+            "{Q}" => ActivationCost::Untap,
+            _ if MANA_COST_REGEX.is_match(cost) => ActivationCost::Mana(cost.to_owned()),
+            _ => {
+                if let Some(life) = PAY_LIFE_REGEX
+                    .captures(cost)
+                    .and_then(|caps| parse_number(&caps[1]))
+                {
+                    ActivationCost::PayLife(life)
+                } else if cost.starts_with("Sacrifice") {
+                    ActivationCost::Sacrifice(cost.to_owned())
+                } else if cost.starts_with("Tap ") && cost.contains("untapped") {
+                    ActivationCost::TapUntapped(cost.to_owned())
+                } else if cost.starts_with("Discard") {
+                    ActivationCost::Discard(cost.to_owned())
+                } else if cost.starts_with("Remove") && cost.contains("counter") {
+                    ActivationCost::RemoveCounters(cost.to_owned())
+                } else {
+                    ActivationCost::Other(cost.to_owned())
+                }
+            }
+        }
+    }
+
+    fn kind(&self) -> Option<CostKind> {
+        match self {
+            ActivationCost::Tap => Some(CostKind::Tap),
+            ActivationCost::Mana(_) => Some(CostKind::Mana),
+            ActivationCost::PayLife(_) => Some(CostKind::Life),
+            ActivationCost::Sacrifice(_) => Some(CostKind::Sacrifice),
+            ActivationCost::TapUntapped(_) => Some(CostKind::TapUntapped),
+            ActivationCost::Discard(_) => Some(CostKind::Discard),
+            ActivationCost::RemoveCounters(_) => Some(CostKind::RemoveCounters),
+            ActivationCost::Untap | ActivationCost::Other(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ManaAbility {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.amount {
+            ManaAmount::Fixed(amount) => write!(fmt, "adds {}", amount)?,
+            ManaAmount::Variable => write!(fmt, "adds X")?,
+        }
+        let mut colors: Vec<_> = self.colors.iter().map(|color| color.name()).collect();
+        if self.colorless {
+            colors.push("Colorless");
+        }
+        if self.commander_color {
+            colors.push("commander color");
+        } else if self.any_color {
+            colors.push("any color");
+        }
+        write!(fmt, " ({})", itertools::join(colors, "/"))?;
+        if !self.costs.is_empty() {
+            write!(fmt, "; costs {}", itertools::join(&self.costs, ", "))?;
+        }
+        if self.self_damage > 0 {
+            write!(fmt, "; deals {} damage to you", self.self_damage)?;
+        }
+        if let Some(restriction) = &self.restriction {
+            write!(fmt, "; {}", restriction)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ActivationCost {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ActivationCost::Tap => write!(fmt, "{{T}}"),
+            ActivationCost::Untap => write!(fmt, "{{Q}}"),
+            ActivationCost::PayLife(life) => write!(fmt, "pay {} life", life),
+            ActivationCost::Mana(cost)
+            | ActivationCost::Sacrifice(cost)
+            | ActivationCost::TapUntapped(cost)
+            | ActivationCost::Discard(cost)
+            | ActivationCost::RemoveCounters(cost)
+            | ActivationCost::Other(cost) => write!(fmt, "{}", cost),
+        }
+    }
+}
+
+impl CostKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            CostKind::Tap => "tap",
+            CostKind::Mana => "mana",
+            CostKind::Life => "life",
+            CostKind::Sacrifice => "sacrifice",
+            CostKind::TapUntapped => "tap-untapped",
+            CostKind::Discard => "discard",
+            CostKind::RemoveCounters => "remove-counters",
+        }
+    }
+}
+
+impl ManaAbilityFilter {
+    pub fn matches(&self, ability: &ManaAbility) -> bool {
+        let amount_matches = match (self.min_amount, ability.amount) {
+            (None, _) | (Some(_), ManaAmount::Variable) => true,
+            (Some(min), ManaAmount::Fixed(amount)) => amount >= min,
+        };
+        amount_matches
+            && self.costs.iter().all(|&kind| ability.has_cost(kind))
+            && self
+                .restricted
+                .is_none_or(|restricted| ability.restriction.is_some() == restricted)
+            && self
+                .any_color
+                .is_none_or(|any_color| ability.any_color == any_color)
+    }
+}
+
+impl std::fmt::Display for ManaAbilityFilter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut criteria = Vec::new();
+        if let Some(min) = self.min_amount {
+            criteria.push(format!("adds {}+ mana", min));
+        }
+        for cost in self.costs.iter() {
+            criteria.push(format!("costs {}", cost.name()));
+        }
+        match self.restricted {
+            Some(true) => criteria.push("restricted".to_owned()),
+            Some(false) => criteria.push("unrestricted".to_owned()),
+            None => {}
+        }
+        match self.any_color {
+            Some(true) => criteria.push("any color".to_owned()),
+            Some(false) => criteria.push("not any color".to_owned()),
+            None => {}
+        }
+        write!(fmt, "{}", itertools::join(criteria, ", "))
+    }
+}
+
+impl EntersTapped {
+    pub fn parse(oracle_text: &str) -> EntersTapped {
+        oracle_text
+            .lines()
+            .map(strip_reminder_text)
+            .map(|line| EntersTapped::parse_line(&line))
+            .find(|enters_tapped| *enters_tapped != EntersTapped::Untapped)
+            .unwrap_or_default()
+    }
+
+    fn parse_line(line: &str) -> EntersTapped {
+        if let Some(life) = ETB_PAY_LIFE_REGEX
+            .captures(line)
+            .and_then(|caps| parse_number(&caps[1]))
+        {
+            EntersTapped::UnlessPayLife(life)
+        } else if let Some(caps) = ETB_REVEAL_REGEX.captures(line) {
+            EntersTapped::UnlessReveal(split_alternatives(&caps[1]))
+        } else if let Some(lands) = ETB_IF_LANDS_REGEX
+            .captures(line)
+            .and_then(|caps| parse_number(&caps[1]))
+        {
+            EntersTapped::UnlessAtMostLands(lands.saturating_sub(1))
+        } else if let Some(caps) = ETB_TAPPED_REGEX.captures(line) {
+            match caps.get(1) {
+                Some(condition) => EntersTapped::parse_condition(condition.as_str()),
+                None => EntersTapped::Tapped,
+            }
+        } else {
+            EntersTapped::Untapped
+        }
+    }
+
+    fn parse_condition(condition: &str) -> EntersTapped {
+        let number = |regex: &Regex| {
+            regex
+                .captures(condition)
+                .and_then(|caps| parse_number(&caps[1]))
+        };
+        if let Some(lands) = number(&AT_MOST_LANDS_REGEX) {
+            return EntersTapped::UnlessAtMostLands(lands);
+        }
+        if let Some(lands) = number(&AT_LEAST_LANDS_REGEX) {
+            return EntersTapped::UnlessAtLeastLands(lands);
+        }
+        if let Some(opponents) = number(&OPPONENTS_REGEX) {
+            return EntersTapped::UnlessOpponents(opponents);
+        }
+        if let Some(caps) = CONTROL_REGEX.captures(condition) {
+            let land_types = split_alternatives(&caps[1]);
+            if land_types
+                .iter()
+                .all(|land_type| BASIC_LAND_TYPES.contains(&land_type.as_str()))
+            {
+                return EntersTapped::UnlessLandType(land_types);
+            }
+        }
+        EntersTapped::Unless(condition.to_owned())
+    }
+
+    pub fn condition(&self) -> EntersTappedCondition {
+        match self {
+            EntersTapped::Untapped => EntersTappedCondition::Untapped,
+            EntersTapped::Tapped => EntersTappedCondition::Tapped,
+            EntersTapped::UnlessLandType(_) => EntersTappedCondition::UnlessLandType,
+            EntersTapped::UnlessAtMostLands(_) => EntersTappedCondition::UnlessAtMostLands,
+            EntersTapped::UnlessAtLeastLands(_) => EntersTappedCondition::UnlessAtLeastLands,
+            EntersTapped::UnlessOpponents(_) => EntersTappedCondition::UnlessOpponents,
+            EntersTapped::UnlessPayLife(_) => EntersTappedCondition::UnlessPayLife,
+            EntersTapped::UnlessReveal(_) => EntersTappedCondition::UnlessReveal,
+            EntersTapped::Unless(_) => EntersTappedCondition::Unless,
+        }
+    }
+
+    pub fn count(&self) -> Option<u32> {
+        match self {
+            EntersTapped::UnlessLandType(types) | EntersTapped::UnlessReveal(types) => {
+                Some(types.len() as u32)
+            }
+            EntersTapped::UnlessAtMostLands(count)
+            | EntersTapped::UnlessAtLeastLands(count)
+            | EntersTapped::UnlessOpponents(count)
+            | EntersTapped::UnlessPayLife(count) => Some(*count),
+            EntersTapped::Untapped | EntersTapped::Tapped | EntersTapped::Unless(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EntersTapped {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EntersTapped::Untapped => write!(fmt, "untapped"),
+            EntersTapped::Tapped => write!(fmt, "tapped"),
+            EntersTapped::UnlessLandType(types) => {
+                write!(fmt, "tapped unless you control {}", alternatives(types))
+            }
+            EntersTapped::UnlessAtMostLands(lands) => write!(
+                fmt,
+                "tapped unless you control {} or fewer other lands",
+                lands
+            ),
+            EntersTapped::UnlessAtLeastLands(lands) => write!(
+                fmt,
+                "tapped unless you control {} or more other lands",
+                lands
+            ),
+            EntersTapped::UnlessOpponents(opponents) => write!(
+                fmt,
+                "tapped unless you have {} or more opponents",
+                opponents
+            ),
+            EntersTapped::UnlessPayLife(life) => write!(fmt, "tapped unless you pay {} life", life),
+            EntersTapped::UnlessReveal(types) => {
+                write!(fmt, "tapped unless you reveal {} card", alternatives(types))
+            }
+            EntersTapped::Unless(condition) => write!(fmt, "tapped unless {}", condition),
+        }
+    }
+}
+
+impl EntersTappedFilter {
+    pub fn matches(&self, enters_tapped: &EntersTapped) -> bool {
+        match *self {
+            EntersTappedFilter::Condition(condition) => enters_tapped.condition() == condition,
+            EntersTappedFilter::Rule { condition, count } => {
+                enters_tapped.condition() == condition
+                    && count.is_none_or(|count| enters_tapped.count() == Some(count))
+            }
+        }
+    }
+}
+
+impl FetchFilter {
+    /// Parses every search for land cards out of a card face's oracle text, including
+    /// landcycling keywords. Searches that could find cards other than lands are skipped.
+    pub fn parse_all(oracle_text: &str) -> Vec<FetchFilter> {
+        oracle_text
+            .lines()
+            .flat_map(|line| {
+                let mut filters = Vec::new();
+                if let Some(caps) = LANDCYCLING_REGEX.captures(line.trim()) {
+                    filters.push(match &caps[1] {
+                        "Basic land" => FetchFilter::new(true, Vec::new()),
+                        "Land" => FetchFilter::new(false, Vec::new()),
+                        land_type => FetchFilter::new(false, vec![land_type.to_owned()]),
+                    });
+                }
+                let line = strip_reminder_text(line);
+                for caps in SEARCH_REGEX.captures_iter(&line) {
+                    if let Some(mut filter) = FetchFilter::parse(&caps[1]) {
+                        if caps.get(3).is_some() {
+                            filter.types = BASIC_LAND_TYPES.iter().map(|&t| t.to_owned()).collect();
+                        }
+                        filters.push(filter);
+                    }
+                    filters.extend(caps.get(2).and_then(|and| FetchFilter::parse(and.as_str())));
+                }
+                filters
+            })
+            .collect()
+    }
+
+    fn new(basic: bool, types: Vec<String>) -> FetchFilter {
+        FetchFilter {
+            basic,
+            snow: false,
+            types,
+        }
+    }
+
+    fn parse(searched: &str) -> Option<FetchFilter> {
+        let mut filter = FetchFilter::new(false, Vec::new());
+        let mut land = false;
+        for word in ALTERNATIVES_SPLIT_REGEX
+            .split(searched)
+            .flat_map(str::split_whitespace)
+        {
+            match word {
+                "basic" => filter.basic = true,
+                "snow" => filter.snow = true,
+                "land" => land = true,
+                _ if word.starts_with(char::is_uppercase) => filter.types.push(word.to_owned()),
+                _ => return None,
+            }
+        }
+        Some(filter).filter(|filter| land || !filter.types.is_empty())
+    }
+
+    /// Whether the search can find any land at all rather than particular ones.
+    pub fn is_any_land(&self) -> bool {
+        !self.basic && !self.snow && self.types.is_empty()
+    }
+
+    /// Whether a card with this type line is a land the search can find. Only the front face of
+    /// a double-faced card counts.
+    pub fn matches(&self, type_line: &str) -> bool {
+        let front = type_line.split(" // ").next().unwrap_or_default();
+        let (types, subtypes) = front.split_once(" — ").unwrap_or((front, ""));
+        types.contains("Land")
+            && (!self.basic || types.contains("Basic"))
+            && (!self.snow || types.contains("Snow"))
+            && (self.types.is_empty()
+                || subtypes
+                    .split_whitespace()
+                    .any(|subtype| self.types.iter().any(|t| t == subtype)))
+    }
+}
+
+impl std::fmt::Display for FetchFilter {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.basic {
+            write!(fmt, "basic ")?;
+        }
+        if self.snow {
+            write!(fmt, "snow ")?;
+        }
+        if self.types.is_empty() {
+            write!(fmt, "land")
+        } else {
+            write!(fmt, "{}", self.types.join(" or "))
+        }
+    }
+}
+
+fn split_alternatives(text: &str) -> Vec<String> {
+    ALTERNATIVES_SPLIT_REGEX
+        .split(text)
+        .map(|item| {
+            item.trim_start_matches("a ")
+                .trim_start_matches("an ")
+                .to_owned()
+        })
+        .collect()
+}
+
+fn alternatives(items: &[String]) -> String {
+    match items.first() {
+        Some(first) if first.starts_with(['A', 'E', 'I', 'O', 'U']) => {
+            format!("an {}", items.join(" or "))
+        }
+        _ => format!("a {}", items.join(" or ")),
+    }
+}
+
+fn strip_reminder_text(line: &str) -> String {
+    let line = line.trim();
+    match line.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+        // Basic lands have only reminder text for their mana ability
+        Some(inner) if !inner.contains('(') => inner.to_owned(),
+        _ => REMINDER_TEXT_REGEX.replace_all(line, "").into_owned(),
+    }
+}
+
+fn self_damage(text: &str) -> Option<u32> {
+    SELF_DAMAGE_REGEX
+        .captures(text)
+        .and_then(|caps| parse_number(&caps[1]))
+}
+
+fn parse_number(word: &str) -> Option<u32> {
+    const WORDS: [&str; 11] = [
+        "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    ];
+    word.parse().ok().or_else(|| {
+        WORDS
+            .iter()
+            .position(|w| w.eq_ignore_ascii_case(word))
+            .map(|n| n as u32)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(types: &[&str]) -> Vec<String> {
+        types.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_enters_tapped() {
+        let cases = [
+            (
+                "shock",
+                "({T}: Add {G} or {W}.)\nAs Temple Garden enters the battlefield, you may pay 2 \
+                 life. If you don't, it enters the battlefield tapped.",
+                EntersTapped::UnlessPayLife(2),
+            ),
+            (
+                "shock, current wording",
+                "({T}: Add {G} or {W}.)\nAs Temple Garden enters, you may pay 2 life. If you \
+                 don't, it enters tapped.",
+                EntersTapped::UnlessPayLife(2),
+            ),
+            (
+                "check",
+                "Glacial Fortress enters the battlefield tapped unless you control a Plains or an \
+                 Island.\n{T}: Add {W} or {U}.",
+                EntersTapped::UnlessLandType(types(&["Plains", "Island"])),
+            ),
+            (
+                "fast",
+                "Seachrome Coast enters the battlefield tapped unless you control two or fewer \
+                 other lands.\n{T}: Add {W} or {U}.",
+                EntersTapped::UnlessAtMostLands(2),
+            ),
+            (
+                "slow",
+                "Deserted Beach enters the battlefield tapped unless you control two or more \
+                 other lands.\n{T}: Add {W} or {U}.",
+                EntersTapped::UnlessAtLeastLands(2),
+            ),
+            (
+                "reveal",
+                "As Port Town enters the battlefield, you may reveal a Plains or Island card from \
+                 your hand. If you don't, Port Town enters the battlefield tapped.\n{T}: Add {W} \
+                 or {U}.",
+                EntersTapped::UnlessReveal(types(&["Plains", "Island"])),
+            ),
+            (
+                "bond",
+                "Sea of Clouds enters the battlefield tapped unless you have two or more \
+                 opponents.\n{T}: Add {W} or {U}.",
+                EntersTapped::UnlessOpponents(2),
+            ),
+            (
+                "tapped",
+                "Azorius Guildgate enters the battlefield tapped.\n{T}: Add {W} or {U}.",
+                EntersTapped::Tapped,
+            ),
+            ("untapped", "({T}: Add {W}.)", EntersTapped::Untapped),
+            (
+                "other condition",
+                "Minas Tirith enters the battlefield tapped unless you control a legendary \
+                 creature.\n{T}: Add {W}.",
+                EntersTapped::Unless("you control a legendary creature".to_owned()),
+            ),
+        ];
+        for (name, oracle_text, expected) in cases {
+            assert_eq!(EntersTapped::parse(oracle_text), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_mana_abilities() {
+        // Name, oracle text, and each ability's amount, colors, colorless, any color, life cost
+        // and restriction.
+        type Expected = (ManaAmount, Vec<Color>, bool, bool, u32, bool);
+        let cases: [(&str, &str, Vec<Expected>); 5] = [
+            (
+                "pain land",
+                "{T}: Add {C}.\n{T}: Add {W} or {U}. Adarkar Wastes deals 1 damage to you.",
+                vec![
+                    (ManaAmount::Fixed(1), vec![], true, false, 0, false),
+                    (
+                        ManaAmount::Fixed(1),
+                        vec![Color::White, Color::Blue],
+                        false,
+                        false,
+                        1,
+                        false,
+                    ),
+                ],
+            ),
+            (
+                "City of Brass",
+                "Whenever City of Brass becomes tapped, it deals 1 damage to you.\n{T}: Add one \
+                 mana of any color.",
+                vec![(ManaAmount::Fixed(1), vec![], false, true, 1, false)],
+            ),
+            (
+                "Sol Ring",
+                "{T}: Add {C}{C}.",
+                vec![(ManaAmount::Fixed(2), vec![], true, false, 0, false)],
+            ),
+            (
+                "restricted mana",
+                "{T}: Add {C}.\n{T}: Add one mana of any color. Spend this mana only to cast a \
+                 multicolored spell.",
+                vec![
+                    (ManaAmount::Fixed(1), vec![], true, false, 0, false),
+                    (ManaAmount::Fixed(1), vec![], false, true, 0, true),
+                ],
+            ),
+            (
+                "variable amount",
+                "{T}: Add {G} for each Elf you control.",
+                vec![(
+                    ManaAmount::Variable,
+                    vec![Color::Green],
+                    false,
+                    false,
+                    0,
+                    false,
+                )],
+            ),
+        ];
+        for (name, oracle_text, expected) in cases {
+            let abilities: Vec<Expected> = ManaAbility::parse_all(oracle_text)
+                .iter()
+                .map(|ability| {
+                    (
+                        ability.amount(),
+                        ability.colors.clone(),
+                        ability.colorless,
+                        ability.any_color(),
+                        ability.life_cost(),
+                        ability.restriction.is_some(),
+                    )
+                })
+                .collect();
+            assert_eq!(abilities, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn parse_mana_ability_costs() {
+        let abilities = ManaAbility::parse_all(
+            "{1}, {T}, Pay 1 life, Sacrifice Horizon Canopy: Draw a card.\n{T}, Pay 1 life: Add \
+             {G} or {W}.",
+        );
+        assert_eq!(abilities.len(), 1);
+        assert_eq!(
+            abilities[0].costs(),
+            [ActivationCost::Tap, ActivationCost::PayLife(1)]
+        );
+        assert_eq!(abilities[0].life_cost(), 1);
+    }
+}
@@ -1,8 +1,18 @@
-use crate::color::Color;
+use crate::{
+    color::Color,
+    download::Downloader,
+    source::{read_data, BulkData, CardSource, FetchFuture},
+    tags::CardTags,
+};
 use chrono::prelude::*;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::{borrow::Cow, collections::HashMap};
 
+static DEFAULT_API_URL: &str = "https://api.scryfall.com";
+
+pub static BULK_DATA_TYPES: [&str; 3] = ["oracle-cards", "default-cards", "unique-artwork"];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CardList<'a>(#[serde(borrow)] Vec<Card<'a>>);
 
@@ -16,6 +26,8 @@ pub struct Card<'a> {
     pub card_faces: Option<Vec<CardFace<'a>>>,
     #[serde(default)]
     pub cmc: f32,
+    #[serde(default, borrow)]
+    pub mana_cost: Option<Cow<'a, str>>,
     pub color_identity: Vec<Color>,
     #[serde(borrow)]
     pub name: Cow<'a, str>,
@@ -25,7 +37,19 @@ pub struct Card<'a> {
     pub image_uris: Option<HashMap<Cow<'a, str>, Cow<'a, str>>>,
     pub set_type: SetType,
     #[serde(default)]
+    pub digital: bool,
+    #[serde(default)]
     pub legalities: Option<HashMap<Format, Legality>>,
+    #[serde(default, borrow)]
+    pub set: Option<Cow<'a, str>>,
+    #[serde(default)]
+    pub released_at: Option<NaiveDate>,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default, borrow)]
+    pub produced_mana: Option<Vec<Cow<'a, str>>>,
+    #[serde(default, borrow)]
+    pub oracle_text: Option<Cow<'a, str>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -35,7 +59,29 @@ pub struct CardFace<'a> {
     #[serde(borrow)]
     pub name: Cow<'a, str>,
     #[serde(default, borrow)]
+    pub mana_cost: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
     pub type_line: Option<Cow<'a, str>>,
+    #[serde(default, borrow)]
+    pub oracle_text: Option<Cow<'a, str>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    #[default]
+    Normal,
+    Split,
+    Flip,
+    Transform,
+    ModalDfc,
+    Meld,
+    Adventure,
+    Battle,
+    ReversibleCard,
+    DoubleFacedToken,
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -78,12 +124,225 @@ pub struct BulkDataInfo {
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug)]
+pub struct ScryfallSource {
+    api_url: String,
+    bulk_data_type: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrintingPolicy {
+    Oldest,
+    Newest,
+    Set(String),
+}
+
 impl<'a> CardList<'a> {
     pub fn cards(&'a self) -> &'a Vec<Card<'a>> {
         &self.0
     }
 }
 
+impl<'a> CardList<'a> {
+    pub fn select_printings(self, policy: &PrintingPolicy, card_tags: &CardTags) -> CardList<'a> {
+        if let PrintingPolicy::Set(set) = policy {
+            if !self.0.iter().any(|card| card.is_in_set(set)) {
+                warn!(
+                    "--printing {} matches no set in the card data, expected oldest, newest or a \
+                     set code",
+                    set
+                );
+            }
+        }
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut printings: Vec<Vec<Card<'a>>> = Vec::new();
+        for card in self.0 {
+            if let Some(&i) = index.get(card.name.as_ref()) {
+                printings[i].push(card);
+            } else {
+                index.insert(card.name.to_string(), printings.len());
+                printings.push(vec![card]);
+            }
+        }
+        CardList(
+            printings
+                .into_iter()
+                .filter_map(|cards| {
+                    let set = card_tags.get_set(&cards[0].name);
+                    policy.select(cards, set)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl<'a> From<Vec<Card<'a>>> for CardList<'a> {
+    fn from(cards: Vec<Card<'a>>) -> Self {
+        CardList(cards)
+    }
+}
+
+impl ScryfallSource {
+    pub fn new(api_url: Option<&str>, bulk_data_type: &str) -> ScryfallSource {
+        ScryfallSource {
+            api_url: api_url
+                .unwrap_or(DEFAULT_API_URL)
+                .trim_end_matches('/')
+                .to_owned(),
+            bulk_data_type: bulk_data_type.to_owned(),
+        }
+    }
+}
+
+impl PrintingPolicy {
+    /// Picks one printing of a card, from the set given in card-tags.toml or by the policy if the
+    /// card has a printing there. Otherwise digital-only, memorabilia and funny printings are
+    /// only picked when the card has no other printings.
+    fn select<'a>(&self, mut cards: Vec<Card<'a>>, set: Option<&str>) -> Option<Card<'a>> {
+        if cards.len() <= 1 {
+            return cards.pop();
+        }
+        let set = match (set, self) {
+            (Some(set), _) => Some(set),
+            (None, PrintingPolicy::Set(set)) => Some(set.as_str()),
+            _ => None,
+        };
+        if let Some(set) = set {
+            if let Some(i) = cards.iter().position(|card| card.is_in_set(set)) {
+                return Some(cards.swap_remove(i));
+            }
+            if self != &PrintingPolicy::Set(set.to_owned()) {
+                warn!("card \"{}\" has no printing in set {}", &cards[0].name, set);
+            }
+        }
+        if cards.iter().any(Card::is_regular_printing) {
+            cards.retain(Card::is_regular_printing);
+        }
+        let i = match self {
+            PrintingPolicy::Oldest => cards
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, card)| card.released_at)
+                .map(|(i, _)| i),
+            _ => cards
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, card)| card.released_at)
+                .map(|(i, _)| i),
+        }?;
+        Some(cards.swap_remove(i))
+    }
+}
+
+impl From<&str> for PrintingPolicy {
+    fn from(value: &str) -> Self {
+        match value {
+            "oldest" => PrintingPolicy::Oldest,
+            "newest" => PrintingPolicy::Newest,
+            set => PrintingPolicy::Set(set.to_lowercase()),
+        }
+    }
+}
+
+impl<'a> Card<'a> {
+    pub fn produced_colors(&self) -> Vec<Color> {
+        self.produced_mana
+            .iter()
+            .flatten()
+            .filter_map(|symbol| Color::from_symbol(symbol))
+            .collect()
+    }
+
+    pub fn produces_colorless(&self) -> bool {
+        self.produced_mana
+            .iter()
+            .flatten()
+            .any(|symbol| symbol == "C")
+    }
+
+    /// The mana cost of the card, or of its front face when each face has its own.
+    pub fn front_mana_cost(&self) -> Option<&str> {
+        self.mana_cost.as_deref().or_else(|| {
+            self.card_faces
+                .iter()
+                .flatten()
+                .next()
+                .and_then(|face| face.mana_cost.as_deref())
+        })
+    }
+
+    /// Whether the printing is from a normal paper set, rather than a digital-only, memorabilia
+    /// or funny one.
+    pub fn is_regular_printing(&self) -> bool {
+        !self.digital && self.set_type == SetType::Other
+    }
+
+    pub fn is_in_set(&self, set: &str) -> bool {
+        self.set
+            .as_deref()
+            .filter(|s| s.eq_ignore_ascii_case(set))
+            .is_some()
+    }
+}
+
+impl CardSource for ScryfallSource {
+    fn name(&self) -> &'static str {
+        "Scryfall"
+    }
+
+    fn fetch<'a>(&'a self, downloader: &'a Downloader) -> FetchFuture<'a> {
+        Box::pin(async move {
+            let bulk_data_info: BulkDataInfo = downloader
+                .get_json(&format!(
+                    "{}/bulk-data/{}",
+                    self.api_url, self.bulk_data_type
+                ))
+                .await?;
+
+            info!(
+                "loading Scryfall bulk card data from {}",
+                &bulk_data_info.download_uri
+            );
+            let data = read_data(&downloader.get_bytes(&bulk_data_info.download_uri).await?[..])?;
+            Ok(BulkData {
+                data,
+                updated_at: bulk_data_info.updated_at,
+            })
+        })
+    }
+
+    fn parse<'a>(&self, data: &'a str) -> anyhow::Result<CardList<'a>> {
+        Ok(serde_json::from_str(data)?)
+    }
+}
+
+impl Format {
+    pub const ALL: [Format; 9] = [
+        Format::Standard,
+        Format::Historic,
+        Format::Pioneer,
+        Format::Modern,
+        Format::Legacy,
+        Format::Vintage,
+        Format::Pauper,
+        Format::Commander,
+        Format::Brawl,
+    ];
+}
+
+impl Layout {
+    pub fn has_face_images(self) -> bool {
+        matches!(
+            self,
+            Layout::Transform
+                | Layout::ModalDfc
+                | Layout::Battle
+                | Layout::ReversibleCard
+                | Layout::DoubleFacedToken
+        )
+    }
+}
+
 impl SetType {
     pub fn filter_class(self) -> &'static str {
         match self {
@@ -130,3 +389,58 @@ impl std::fmt::Display for Legality {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PRINTINGS: &str = r#"[
+        {"set": "lea", "released_at": "1993-08-05", "set_type": "core"},
+        {"set": "c21", "released_at": "2021-04-23", "set_type": "commander"},
+        {"set": "30a", "released_at": "2022-11-28", "set_type": "memorabilia"},
+        {"set": "ugl", "released_at": "1998-08-11", "set_type": "funny"},
+        {"set": "pz2", "released_at": "2023-01-01", "set_type": "treasure_chest", "digital": true}
+    ]"#;
+
+    // Card data for Sol Ring printings in the given sets.
+    fn printings(sets: &[&str]) -> String {
+        let printings: Vec<serde_json::Map<String, serde_json::Value>> =
+            serde_json::from_str(PRINTINGS).unwrap();
+        let cards: Vec<_> = printings
+            .into_iter()
+            .filter(|printing| sets.contains(&printing["set"].as_str().unwrap()))
+            .map(|mut printing| {
+                printing.insert("id".into(), printing["set"].clone());
+                printing.insert("scryfall_uri".into(), "https://scryfall.com/".into());
+                printing.insert("name".into(), "Sol Ring".into());
+                printing.insert("color_identity".into(), serde_json::json!([]));
+                printing
+            })
+            .collect();
+        serde_json::to_string(&cards).unwrap()
+    }
+
+    fn selected(policy: &str, sets: &[&str], set: Option<&str>) -> String {
+        let data = printings(sets);
+        let cards = serde_json::from_str(&data).unwrap();
+        let card = PrintingPolicy::from(policy).select(cards, set).unwrap();
+        card.set.unwrap().into_owned()
+    }
+
+    #[test]
+    fn select_printing() {
+        let all = ["lea", "c21", "30a", "ugl", "pz2"];
+        assert_eq!(selected("newest", &all, None), "c21");
+        assert_eq!(selected("oldest", &all, None), "lea");
+        assert_eq!(selected("newst", &all, None), "c21");
+        assert_eq!(selected("30a", &all, None), "30a");
+        assert_eq!(selected("newest", &all, Some("pz2")), "pz2");
+        assert_eq!(selected("oldest", &all, Some("ugl")), "ugl");
+    }
+
+    #[test]
+    fn select_special_printing_without_others() {
+        assert_eq!(selected("newest", &["30a", "pz2"], None), "pz2");
+        assert_eq!(selected("oldest", &["30a", "ugl"], None), "ugl");
+    }
+}
@@ -0,0 +1,531 @@
+use crate::{
+    color::Color,
+    deck::Deck,
+    oracle::{ActivationCost, EntersTapped, ManaAmount},
+};
+use lazy_static::lazy_static;
+use rand::{seq::SliceRandom, Rng};
+use regex::Regex;
+
+lazy_static! {
+    static ref COST_SYMBOL_REGEX: Regex = Regex::new(r"\{([^}]+)\}").unwrap();
+}
+
+pub const TURNS: usize = 6;
+const HAND_SIZE: usize = 7;
+
+/// An amount of mana to have untapped, with colored pips that each need a source of their color
+/// and colorless pips that each need a source of colorless mana.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManaRequirement {
+    generic: u32,
+    colorless: u32,
+    pips: Vec<Color>,
+}
+
+/// Plays out opening hands and land drops of a deck to estimate how often it has mana available.
+/// Only lands are played. Lands that produce no mana themselves, such as fetch lands, count as
+/// nothing, and enters-tapped conditions the oracle parser doesn't understand count as tapped.
+#[derive(Debug)]
+pub struct Simulator<'d> {
+    lands: Vec<SimLand<'d>>,
+    library: Vec<Option<usize>>,
+    opponents: u32,
+    on_the_draw: bool,
+}
+
+#[derive(Debug)]
+struct SimLand<'d> {
+    colors: Vec<Color>,
+    colorless: bool,
+    amount: usize,
+    type_line: &'d str,
+    enters_tapped: &'d EntersTapped,
+}
+
+impl ManaRequirement {
+    /// Parses a requirement such as `1WW`, `{1}{W}{W}` or `CC`.
+    pub fn parse(text: &str) -> anyhow::Result<ManaRequirement> {
+        let mut generic = String::new();
+        let mut colorless = 0;
+        let mut pips = Vec::new();
+        for symbol in text.chars().filter(|c| *c != '{' && *c != '}') {
+            match symbol {
+                '0'..='9' => generic.push(symbol),
+                'C' | 'c' => colorless += 1,
+                _ => pips.push(
+                    Color::from_symbol(&symbol.to_ascii_uppercase().to_string()).ok_or_else(
+                        || anyhow::anyhow!("unknown mana symbol '{}' in \"{}\"", symbol, text),
+                    )?,
+                ),
+            }
+        }
+        pips.sort_unstable();
+        Ok(ManaRequirement {
+            generic: generic.parse().unwrap_or_default(),
+            colorless,
+            pips,
+        })
+    }
+
+    /// Reads the generic, colorless and colored mana of a card's cost, or of the first half of a
+    /// split card. X costs nothing, and hybrid, phyrexian and snow symbols count as generic.
+    pub fn from_mana_cost(mana_cost: &str) -> ManaRequirement {
+        let mut requirement = ManaRequirement {
+            generic: 0,
+            colorless: 0,
+            pips: Vec::new(),
+        };
+        let front = mana_cost.split(" // ").next().unwrap_or_default();
+        for caps in COST_SYMBOL_REGEX.captures_iter(front) {
+            let symbol = &caps[1];
+            if let Ok(generic) = symbol.parse::<u32>() {
+                requirement.generic += generic;
+            } else if symbol == "C" {
+                requirement.colorless += 1;
+            } else if let Some(color) = Color::from_symbol(symbol) {
+                requirement.pips.push(color);
+            } else if !matches!(symbol, "X" | "Y" | "Z") {
+                requirement.generic += 1;
+            }
+        }
+        requirement.pips.sort_unstable();
+        requirement
+    }
+
+    pub fn from_color(color: Color) -> ManaRequirement {
+        ManaRequirement {
+            generic: 0,
+            colorless: 0,
+            pips: vec![color],
+        }
+    }
+
+    pub fn pips_of(&self, color: Color) -> u32 {
+        self.pips.iter().filter(|pip| **pip == color).count() as u32
+    }
+
+    pub fn total(&self) -> usize {
+        self.generic as usize + self.colorless as usize + self.pips.len()
+    }
+}
+
+impl std::fmt::Display for ManaRequirement {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.generic > 0 || self.total() == 0 {
+            write!(fmt, "{{{}}}", self.generic)?;
+        }
+        for _ in 0..self.colorless {
+            write!(fmt, "{{C}}")?;
+        }
+        for pip in &self.pips {
+            write!(fmt, "{{{}}}", pip.symbol())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'d> Simulator<'d> {
+    pub fn new(deck: &'d Deck<'_, '_>, opponents: u32, on_the_draw: bool) -> Simulator<'d> {
+        let mut lands = Vec::new();
+        let mut library = Vec::new();
+        for deck_card in deck.cards() {
+            let land = match (deck_card.card(), deck_card.traits()) {
+                (Some(card), Some(traits)) if deck_card.is_land() => {
+                    let colors = card.produced_colors();
+                    let colorless = card.produces_colorless();
+                    let produces_mana = !colors.is_empty() || colorless;
+                    lands.push(SimLand {
+                        colors,
+                        colorless,
+                        amount: if produces_mana {
+                            traits
+                                .mana_abilities()
+                                .iter()
+                                .filter(|ability| {
+                                    ability.costs().contains(&ActivationCost::Tap)
+                                        && ability.costs().iter().all(|cost| {
+                                            matches!(
+                                                cost,
+                                                ActivationCost::Tap | ActivationCost::PayLife(_)
+                                            )
+                                        })
+                                })
+                                .filter_map(|ability| match ability.amount() {
+                                    ManaAmount::Fixed(amount) => Some(amount as usize),
+                                    ManaAmount::Variable => None,
+                                })
+                                .max()
+                                .unwrap_or(1)
+                        } else {
+                            0
+                        },
+                        type_line: traits.type_line(),
+                        enters_tapped: traits.enters_tapped(),
+                    });
+                    Some(lands.len() - 1)
+                }
+                _ => None,
+            };
+            for _ in 0..deck_card.count() {
+                library.push(land);
+            }
+        }
+        Simulator {
+            lands,
+            library,
+            opponents,
+            on_the_draw,
+        }
+    }
+
+    /// The colors the deck's lands can produce.
+    pub fn colors(&self) -> Vec<Color> {
+        let mut colors: Vec<_> = self
+            .lands
+            .iter()
+            .flat_map(|land| land.colors.iter().copied())
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+        colors
+    }
+
+    /// Returns the probability of having the required mana untapped on each turn.
+    pub fn run<R: Rng>(
+        &self,
+        requirement: &ManaRequirement,
+        trials: u32,
+        rng: &mut R,
+    ) -> [f64; TURNS] {
+        let mut library = self.library.clone();
+        let mut successes = [0u32; TURNS];
+        for _ in 0..trials {
+            library.shuffle(rng);
+            let mut cards = library.iter();
+            let mut hand: Vec<usize> = Vec::new();
+            let mut draw = |hand: &mut Vec<usize>, count: usize| {
+                hand.extend(cards.by_ref().take(count).flatten());
+            };
+            draw(&mut hand, HAND_SIZE);
+
+            let mut battlefield = Vec::new();
+            for (turn, successes) in successes.iter_mut().enumerate() {
+                if turn > 0 || self.on_the_draw {
+                    draw(&mut hand, 1);
+                }
+                let tapped = self.play_land(&mut hand, &mut battlefield, requirement);
+                let untapped = &battlefield[..battlefield.len() - tapped as usize];
+                if self.can_pay(untapped, requirement) {
+                    *successes += 1;
+                }
+            }
+        }
+        successes.map(|successes| successes as f64 / trials.max(1) as f64)
+    }
+
+    /// Plays the land from hand that best meets the requirement, returning whether it entered
+    /// tapped. A land that enters tapped is preferred whenever its mana isn't needed this turn,
+    /// then one adding more of the required colors, then one letting more of the hand enter
+    /// untapped next turn.
+    fn play_land(
+        &self,
+        hand: &mut Vec<usize>,
+        battlefield: &mut Vec<usize>,
+        requirement: &ManaRequirement,
+    ) -> bool {
+        let mut best = None;
+        for (position, &land) in hand.iter().enumerate() {
+            for &tapped in self.entry_options(land, position, hand, battlefield) {
+                battlefield.push(land);
+                let untapped = &battlefield[..battlefield.len() - tapped as usize];
+                let key = (
+                    self.can_pay(untapped, requirement),
+                    tapped,
+                    self.colors_covered(battlefield, requirement),
+                    hand.iter()
+                        .enumerate()
+                        .filter(|(other, _)| *other != position)
+                        .filter(|(other, &land)| {
+                            self.entry_options(land, *other, hand, battlefield)
+                                .contains(&false)
+                        })
+                        .count(),
+                );
+                battlefield.pop();
+                if best.as_ref().is_none_or(|(best_key, _, _)| key > *best_key) {
+                    best = Some((key, position, tapped));
+                }
+            }
+        }
+        match best {
+            Some((_, position, tapped)) => {
+                battlefield.push(hand.swap_remove(position));
+                tapped
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a land could enter tapped, untapped, or either.
+    fn entry_options(
+        &self,
+        land: usize,
+        position: usize,
+        hand: &[usize],
+        battlefield: &[usize],
+    ) -> &'static [bool] {
+        let untapped = match self.lands[land].enters_tapped {
+            EntersTapped::Untapped => true,
+            EntersTapped::Tapped | EntersTapped::Unless(_) => false,
+            EntersTapped::UnlessLandType(types) => {
+                self.has_land_type(battlefield.iter().copied(), types)
+            }
+            EntersTapped::UnlessAtMostLands(lands) => battlefield.len() <= *lands as usize,
+            EntersTapped::UnlessAtLeastLands(lands) => battlefield.len() >= *lands as usize,
+            EntersTapped::UnlessOpponents(opponents) => self.opponents >= *opponents,
+            EntersTapped::UnlessPayLife(_) => return &[false, true],
+            EntersTapped::UnlessReveal(types) => self.has_land_type(
+                hand.iter()
+                    .enumerate()
+                    .filter(|(other, _)| *other != position)
+                    .map(|(_, &land)| land),
+                types,
+            ),
+        };
+        if untapped {
+            &[false]
+        } else {
+            &[true]
+        }
+    }
+
+    fn has_land_type(&self, mut lands: impl Iterator<Item = usize>, types: &[String]) -> bool {
+        lands.any(|land| {
+            types
+                .iter()
+                .any(|land_type| self.lands[land].type_line.contains(land_type.as_str()))
+        })
+    }
+
+    fn colors_covered(&self, lands: &[usize], requirement: &ManaRequirement) -> usize {
+        let mut colors = requirement.pips.clone();
+        colors.dedup();
+        colors
+            .iter()
+            .filter(|color| {
+                lands
+                    .iter()
+                    .any(|&land| self.lands[land].colors.contains(color))
+            })
+            .count()
+    }
+
+    /// Whether the lands' mana covers the requirement, matching each colored pip to a distinct
+    /// mana of its color and each colorless pip to a distinct colorless mana.
+    fn can_pay(&self, lands: &[usize], requirement: &ManaRequirement) -> bool {
+        let units: Vec<&SimLand> = lands
+            .iter()
+            .flat_map(|&land| {
+                let land = &self.lands[land];
+                std::iter::repeat_n(land, land.amount)
+            })
+            .collect();
+        if units.len() < requirement.total() {
+            return false;
+        }
+        let pips: Vec<_> = requirement
+            .pips
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(std::iter::repeat_n(None, requirement.colorless as usize))
+            .collect();
+        let mut assigned = vec![None; units.len()];
+        (0..pips.len()).all(|pip| {
+            let mut seen = vec![false; units.len()];
+            assign_pip(pip, &pips, &units, &mut assigned, &mut seen)
+        })
+    }
+}
+
+// Finds an augmenting path for a pip in the bipartite matching of pips to mana, where a pip of
+// `None` is colorless.
+fn assign_pip(
+    pip: usize,
+    pips: &[Option<Color>],
+    units: &[&SimLand],
+    assigned: &mut [Option<usize>],
+    seen: &mut [bool],
+) -> bool {
+    for unit in 0..units.len() {
+        let produces = match pips[pip] {
+            Some(color) => units[unit].colors.contains(&color),
+            None => units[unit].colorless,
+        };
+        if seen[unit] || !produces {
+            continue;
+        }
+        seen[unit] = true;
+        if assigned[unit].is_none_or(|other| assign_pip(other, pips, units, assigned, seen)) {
+            assigned[unit] = Some(pip);
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const TRIALS: u32 = 2000;
+
+    fn land<'d>(
+        colors: &[Color],
+        type_line: &'d str,
+        enters_tapped: &'d EntersTapped,
+    ) -> SimLand<'d> {
+        SimLand {
+            colors: colors.to_vec(),
+            colorless: false,
+            amount: 1,
+            type_line,
+            enters_tapped,
+        }
+    }
+
+    // A simulator for a library of the given lands, each with a count, and other cards.
+    fn simulator<'d>(lands: Vec<(SimLand<'d>, usize)>, others: usize) -> Simulator<'d> {
+        let mut library = vec![None; others];
+        for (i, (_, count)) in lands.iter().enumerate() {
+            library.extend(std::iter::repeat_n(Some(i), *count));
+        }
+        Simulator {
+            lands: lands.into_iter().map(|(land, _)| land).collect(),
+            library,
+            opponents: 1,
+            on_the_draw: false,
+        }
+    }
+
+    fn run(simulator: &Simulator, requirement: &str) -> [f64; TURNS] {
+        let requirement = ManaRequirement::parse(requirement).unwrap();
+        simulator.run(&requirement, TRIALS, &mut ChaCha8Rng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn parse_requirement() {
+        let cases = [
+            ("1WW", 1, 0, vec![Color::White, Color::White]),
+            ("{2}{U}{B}", 2, 0, vec![Color::Blue, Color::Black]),
+            ("CC", 0, 2, vec![]),
+            ("10g", 10, 0, vec![Color::Green]),
+            ("GW", 0, 0, vec![Color::White, Color::Green]),
+        ];
+        for (text, generic, colorless, pips) in cases {
+            assert_eq!(
+                ManaRequirement::parse(text).unwrap(),
+                ManaRequirement {
+                    generic,
+                    colorless,
+                    pips
+                },
+                "{}",
+                text
+            );
+        }
+        assert!(ManaRequirement::parse("1X").is_err());
+        assert_eq!(
+            ManaRequirement::parse("2WC").unwrap().to_string(),
+            "{2}{C}{W}"
+        );
+    }
+
+    #[test]
+    fn requirement_from_mana_cost() {
+        let cases = [
+            ("{X}{B}{B}{B}", "{B}{B}{B}"),
+            ("{2}{W/U}{U/P}", "{4}"),
+            ("{1}{R} // {1}{U}", "{1}{R}"),
+            ("{C}{C}", "{C}{C}"),
+            ("", "{0}"),
+        ];
+        for (mana_cost, expected) in cases {
+            assert_eq!(
+                ManaRequirement::from_mana_cost(mana_cost).to_string(),
+                expected,
+                "{}",
+                mana_cost
+            );
+        }
+    }
+
+    #[test]
+    fn untapped_lands_only() {
+        let untapped = EntersTapped::Untapped;
+        let simulator = simulator(
+            vec![(land(&[Color::White], "Basic Land — Plains", &untapped), 10)],
+            0,
+        );
+        assert_eq!(run(&simulator, "W"), [1.0; TURNS]);
+        assert_eq!(run(&simulator, "WW"), [0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(run(&simulator, "U"), [0.0; TURNS]);
+    }
+
+    #[test]
+    fn tapped_lands_are_a_turn_late() {
+        let tapped = EntersTapped::Tapped;
+        let simulator = simulator(vec![(land(&[Color::Blue], "Land", &tapped), 10)], 0);
+        assert_eq!(run(&simulator, "U"), [0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(run(&simulator, "2"), [0.0, 0.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn conditional_lands() {
+        let untapped = EntersTapped::Untapped;
+        let check = EntersTapped::UnlessLandType(vec!["Plains".to_owned(), "Island".to_owned()]);
+        let shock = EntersTapped::UnlessPayLife(2);
+        // Seven cards, so the opening hand is the whole library. The check land enters tapped on
+        // turn 1 either way, and untapped after the Plains.
+        let check_lands = simulator(
+            vec![
+                (land(&[Color::White], "Basic Land — Plains", &untapped), 1),
+                (land(&[Color::White, Color::Blue], "Land", &check), 1),
+            ],
+            5,
+        );
+        assert_eq!(run(&check_lands, "WU"), [0.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(run(&check_lands, "W"), [1.0; TURNS]);
+
+        let shock_lands = simulator(
+            vec![(
+                land(&[Color::White, Color::Blue], "Land — Plains Island", &shock),
+                7,
+            )],
+            0,
+        );
+        assert_eq!(run(&shock_lands, "U"), [1.0; TURNS]);
+    }
+
+    #[test]
+    fn seeded_odds() {
+        let untapped = EntersTapped::Untapped;
+        let tapped = EntersTapped::Tapped;
+        // Three untapped and two tapped lands in a 20-card library, where the chance of an
+        // untapped land in the opening hand is 1 - C(17, 7) / C(20, 7), about 0.749.
+        let simulator = simulator(
+            vec![
+                (land(&[Color::Red], "Basic Land — Mountain", &untapped), 3),
+                (land(&[Color::Red], "Land", &tapped), 2),
+            ],
+            15,
+        );
+        let odds = run(&simulator, "R");
+        assert_eq!(odds, [0.745, 0.934, 0.9645, 0.9765, 0.986, 0.9935]);
+        assert!((odds[0] - 0.749).abs() < 0.01);
+        assert_eq!(run(&simulator, "R"), odds);
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    download::Downloader,
+    mtgjson::MtgJsonSource,
+    scryfall::{CardList, ScryfallSource},
+};
+use chrono::prelude::*;
+use flate2::bufread::MultiGzDecoder;
+use std::{
+    fs::File,
+    future::Future,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    pin::Pin,
+};
+
+pub type FetchFuture<'a> = Pin<Box<dyn Future<Output = anyhow::Result<BulkData>> + 'a>>;
+
+/// A provider of bulk card data that maps into the common card model.
+pub trait CardSource {
+    fn name(&self) -> &'static str;
+
+    fn fetch<'a>(&'a self, downloader: &'a Downloader) -> FetchFuture<'a>;
+
+    fn parse<'a>(&self, data: &'a str) -> anyhow::Result<CardList<'a>>;
+}
+
+#[derive(Debug)]
+pub struct BulkData {
+    pub data: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+static GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+static ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+pub static SOURCE_NAMES: [&str; 2] = ["scryfall", "mtgjson"];
+
+pub fn from_name(
+    name: &str,
+    api_url: Option<&str>,
+    bulk_data_type: &str,
+) -> Option<Box<dyn CardSource>> {
+    match name {
+        "scryfall" => Some(Box::new(ScryfallSource::new(api_url, bulk_data_type))),
+        "mtgjson" => Some(Box::new(MtgJsonSource::new(api_url))),
+        _ => None,
+    }
+}
+
+impl BulkData {
+    pub fn load(path: &Path) -> anyhow::Result<BulkData> {
+        Ok(BulkData {
+            data: read_data(BufReader::new(File::open(path)?))?,
+            updated_at: std::fs::metadata(path)?.modified()?.into(),
+        })
+    }
+}
+
+/// Reads bulk data to a string, transparently decompressing gzip or zstd input.
+pub fn read_data<R: BufRead>(mut reader: R) -> std::io::Result<String> {
+    let mut data = String::new();
+    let magic = reader.fill_buf()?;
+    if magic.starts_with(&GZIP_MAGIC) {
+        MultiGzDecoder::new(reader).read_to_string(&mut data)?;
+    } else if magic.starts_with(&ZSTD_MAGIC) {
+        zstd::Decoder::with_buffer(reader)?.read_to_string(&mut data)?;
+    } else {
+        reader.read_to_string(&mut data)?;
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        color::Colors,
+        scryfall::{Card, Format, Legality},
+    };
+    use std::{borrow::Cow, collections::HashMap};
+
+    static SCRYFALL_CARDS: &str = include_str!("../tests/fixtures/scryfall-cards.json");
+    static MTGJSON_ATOMIC_CARDS: &str = include_str!("../tests/fixtures/mtgjson-atomic-cards.json");
+
+    type FaceFields<'c> = (
+        &'c str,
+        Option<&'c str>,
+        Option<&'c str>,
+        Option<&'c str>,
+        bool,
+    );
+
+    fn faces<'c>(card: &'c Card) -> Option<Vec<FaceFields<'c>>> {
+        card.card_faces.as_ref().map(|faces| {
+            faces
+                .iter()
+                .map(|face| {
+                    (
+                        face.name.as_ref(),
+                        face.mana_cost.as_deref(),
+                        face.type_line.as_deref(),
+                        face.oracle_text.as_deref(),
+                        has_normal_image(&face.image_uris),
+                    )
+                })
+                .collect()
+        })
+    }
+
+    fn has_normal_image(uris: &Option<HashMap<Cow<str>, Cow<str>>>) -> bool {
+        uris.as_ref()
+            .and_then(|uris| uris.get("normal"))
+            .is_some_and(|uri| uri.starts_with("https://"))
+    }
+
+    fn produced_mana<'c>(card: &'c Card) -> Vec<&'c str> {
+        let mut produced: Vec<_> = card
+            .produced_mana
+            .iter()
+            .flatten()
+            .map(Cow::as_ref)
+            .collect();
+        produced.sort_unstable();
+        produced
+    }
+
+    fn legality(card: &Card, format: Format) -> Legality {
+        card.legalities
+            .as_ref()
+            .and_then(|legalities| legalities.get(&format))
+            .copied()
+            .unwrap_or(Legality::NotLegal)
+    }
+
+    #[test]
+    fn sources_map_to_the_same_cards() {
+        let scryfall = ScryfallSource::new(None, "oracle_cards")
+            .parse(SCRYFALL_CARDS)
+            .unwrap();
+        let mtgjson = MtgJsonSource::new(None)
+            .parse(MTGJSON_ATOMIC_CARDS)
+            .unwrap();
+        assert_eq!(scryfall.cards().len(), mtgjson.cards().len());
+
+        for expected in scryfall.cards() {
+            let name = expected.name.as_ref();
+            let card = mtgjson
+                .cards()
+                .iter()
+                .find(|card| card.name == expected.name)
+                .unwrap_or_else(|| panic!("MTGJSON has no card {}", name));
+            assert_eq!(card.layout, expected.layout, "{}", name);
+            assert_eq!(card.cmc, expected.cmc, "{}", name);
+            assert_eq!(card.mana_cost, expected.mana_cost, "{}", name);
+            assert_eq!(card.type_line, expected.type_line, "{}", name);
+            assert_eq!(card.oracle_text, expected.oracle_text, "{}", name);
+            assert_eq!(produced_mana(card), produced_mana(expected), "{}", name);
+            assert_eq!(
+                Colors::from_vec(card.color_identity.clone()),
+                Colors::from_vec(expected.color_identity.clone()),
+                "{}",
+                name
+            );
+            for format in Format::ALL {
+                assert_eq!(
+                    legality(card, format),
+                    legality(expected, format),
+                    "{} in {:?}",
+                    name,
+                    format
+                );
+            }
+            assert_eq!(
+                has_normal_image(&card.image_uris),
+                has_normal_image(&expected.image_uris),
+                "{}",
+                name
+            );
+            assert_eq!(faces(card), faces(expected), "{}", name);
+        }
+    }
+
+    fn load_compressed(test: &str, compressed: &[u8]) -> BulkData {
+        let path =
+            std::env::temp_dir().join(format!("manabase-{}-{}.json", test, std::process::id()));
+        std::fs::write(&path, compressed).unwrap();
+        let bulk_data = BulkData::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bulk_data
+    }
+
+    fn assert_loads_like_plain_file(bulk_data: &BulkData) {
+        let plain = BulkData::load(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/scryfall-cards.json"),
+        )
+        .unwrap();
+        assert_eq!(bulk_data.data, plain.data);
+        assert_eq!(bulk_data.data, SCRYFALL_CARDS);
+
+        let source = ScryfallSource::new(None, "oracle_cards");
+        let names = |data| -> Vec<_> {
+            source
+                .parse(data)
+                .unwrap()
+                .cards()
+                .iter()
+                .map(|card| card.name.to_string())
+                .collect()
+        };
+        assert_eq!(names(&bulk_data.data), names(&plain.data));
+    }
+
+    #[test]
+    fn read_gzip_data() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(SCRYFALL_CARDS.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.starts_with(&GZIP_MAGIC));
+        assert_loads_like_plain_file(&load_compressed("gzip", &compressed));
+
+        // Concatenated gzip members decompress to the concatenated data.
+        let (first, second) = SCRYFALL_CARDS.as_bytes().split_at(SCRYFALL_CARDS.len() / 2);
+        let mut members = Vec::new();
+        for part in [first, second] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(part).unwrap();
+            members.extend(encoder.finish().unwrap());
+        }
+        assert_loads_like_plain_file(&load_compressed("gzip-members", &members));
+    }
+
+    #[test]
+    fn read_zstd_data() {
+        let compressed = zstd::encode_all(SCRYFALL_CARDS.as_bytes(), 0).unwrap();
+        assert!(compressed.starts_with(&ZSTD_MAGIC));
+        assert_loads_like_plain_file(&load_compressed("zstd", &compressed));
+    }
+}
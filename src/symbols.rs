@@ -0,0 +1,139 @@
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    static ref SYMBOL_REGEX: Regex = Regex::new(r"\{([^{}]+)\}").unwrap();
+}
+
+// The two-color hybrid symbols in mana.css, in the order Scryfall writes them.
+static HYBRID_PAIRS: [&str; 10] = ["wu", "wb", "ub", "ur", "br", "bg", "rw", "rg", "gw", "gu"];
+
+/// Renders text containing mana and other symbols such as `{2}{W/U}` or `{T}` as HTML, with each
+/// symbol drawn from mana.css. Everything else is escaped, and symbols mana.css doesn't have are
+/// left as text.
+pub fn render_symbols(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    let mut end = 0;
+    for caps in SYMBOL_REGEX.captures_iter(text) {
+        let symbol = caps.get(0).unwrap();
+        html.push_str(&escape(&text[end..symbol.start()]));
+        html.push_str(&render_symbol(&caps));
+        end = symbol.end();
+    }
+    html.push_str(&escape(&text[end..]));
+    html
+}
+
+/// Renders oracle text with its symbols, one line per paragraph.
+pub fn render_oracle_text(text: &str) -> String {
+    text.lines()
+        .map(render_symbols)
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn render_symbol(caps: &Captures) -> String {
+    match symbol_class(&caps[1]) {
+        Some(class) => format!(
+            "<span class=\"mana s{}\" title=\"{}\"></span>",
+            class,
+            escape(&caps[0])
+        ),
+        None => escape(&caps[0]),
+    }
+}
+
+// The mana.css class suffix for a symbol: generic mana up to 20, X, Y and Z, the five colors,
+// colorless, snow, tap, untap, energy, hybrid, two-generic hybrid and phyrexian mana.
+fn symbol_class(symbol: &str) -> Option<String> {
+    if let Ok(generic) = symbol.parse::<u32>() {
+        return Some(generic.to_string()).filter(|_| generic <= 20);
+    }
+    let class = symbol.replace('/', "").to_lowercase();
+    let is_color = |c: char| "wubrg".contains(c);
+    let mut chars = class.chars();
+    let valid = match (chars.next(), chars.next(), chars.next()) {
+        (Some(c), None, _) => "wubrgcsxyztqe".contains(c),
+        (Some('2'), Some(c), None) => is_color(c),
+        (Some(c), Some('p'), None) => is_color(c),
+        (Some(_), Some(_), None) => HYBRID_PAIRS.contains(&class.as_str()),
+        _ => false,
+    };
+    Some(class).filter(|_| valid)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(class: &str, text: &str) -> String {
+        format!("<span class=\"mana s{}\" title=\"{}\"></span>", class, text)
+    }
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            render_symbols(r#"Choose "Fish & Chips" if X < 3 or X > 5, it's yours."#),
+            "Choose &quot;Fish &amp; Chips&quot; if X &lt; 3 or X &gt; 5, it&#x27;s yours."
+        );
+    }
+
+    #[test]
+    fn renders_symbols() {
+        assert_eq!(
+            render_symbols("{2}{W/U}{G/P}"),
+            format!(
+                "{}{}{}",
+                symbol("2", "{2}"),
+                symbol("wu", "{W/U}"),
+                symbol("gp", "{G/P}")
+            )
+        );
+        assert_eq!(
+            render_symbols("{T}: Add {S}. Pay {E}{E}."),
+            format!(
+                "{}: Add {}. Pay {}{}.",
+                symbol("t", "{T}"),
+                symbol("s", "{S}"),
+                symbol("e", "{E}"),
+                symbol("e", "{E}")
+            )
+        );
+        assert_eq!(render_symbols("{2/B}"), symbol("2b", "{2/B}"));
+        assert_eq!(render_symbols("{20}"), symbol("20", "{20}"));
+    }
+
+    #[test]
+    fn unknown_symbols_are_escaped_text() {
+        assert_eq!(render_symbols("{21}{U/W}{H}"), "{21}{U/W}{H}");
+        assert_eq!(render_symbols("{<b>}"), "{&lt;b&gt;}");
+        assert_eq!(symbol_class("W/B/G"), None);
+    }
+
+    #[test]
+    fn oracle_text_lines() {
+        assert_eq!(
+            render_oracle_text("Flying\n{T}: Add {C}."),
+            format!(
+                "Flying<br>{}: Add {}.",
+                symbol("t", "{T}"),
+                symbol("c", "{C}")
+            )
+        );
+    }
+}
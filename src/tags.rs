@@ -1,44 +1,72 @@
 use crate::{
-    color::{Color, Colors},
+    card::CardTraits,
+    color::{Color, Colors, DualColor, Mana, ManaSymbol, TriColor},
+    oracle::{EntersTappedFilter, ManaAbility, ManaAbilityFilter},
     scryfall::{Card, Format, Legality},
 };
+use anyhow::Context;
 use itertools::free::join;
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, info, warn};
 use regex::{Regex, RegexBuilder};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     borrow::Cow,
     collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     hash::{Hash, Hasher},
     ops::Deref,
-    path::Path,
+    path::{Path, PathBuf},
     ptr,
+    sync::Arc,
 };
 
 lazy_static! {
-    static ref TAG_NAME_STRIP_REGEX: Regex = Regex::new(r"[^-\w]").unwrap();
+    static ref TAG_SLUG_STRIP_REGEX: Regex = Regex::new(r"[\W_]+").unwrap();
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum TagKind {
     ColorIdentity,
     ManaPool,
     Cost,
-    #[serde(rename = "type")]
     TypeLine,
     Format,
+    #[default]
     Other,
+    Custom(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct KindData {
+    kind: TagKind,
+    name: String,
+    class: String,
+    sort: KindSort,
+    position: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KindSort {
+    Name,
+    ColorIdentity,
+    Mana,
+    Cmc,
 }
 
 #[derive(Debug)]
 pub struct TagDb<'a> {
-    kind_index: BTreeMap<TagKind, Vec<TagRef<'a>>>,
+    kind_index: Vec<(&'a KindData, Vec<TagRef<'a>>)>,
+    parent_index: HashMap<TagRef<'a>, Vec<TagRef<'a>>>,
 }
 
 #[derive(Debug)]
-pub struct TagIndex(HashMap<String, TagData>);
+pub struct TagIndex {
+    tags: HashMap<String, TagData>,
+    kinds: HashMap<TagKind, Arc<KindData>>,
+    aliases: HashMap<String, String>,
+}
 
 #[derive(Debug)]
 pub struct TagData {
@@ -46,21 +74,108 @@ pub struct TagData {
     alt_names: BTreeSet<String>,
     description: Option<String>,
     subtags: BTreeSet<String>,
-    canonical_name: String,
-    kind: TagKind,
-    cmc: Option<f32>,
+    implies: BTreeSet<String>,
+    slug: String,
+    kind: Arc<KindData>,
+    cmc: Option<CmcRange>,
     type_regex: Option<Regex>,
     color_identity: Option<Colors>,
-    mana: Option<Colors>,
+    color_identity_mode: ColorIdentityMode,
+    mana: Option<Mana>,
+    mana_mode: Option<ManaMode>,
+    mana_ability: Option<ManaAbilityRule>,
+    enters_tapped: Option<EntersTappedRule>,
+    cycle: Option<CycleKind>,
     format: Option<(Format, Legality)>,
+    include: bool,
+}
+
+#[derive(Debug)]
+struct ManaAbilityRule {
+    filter: ManaAbilityFilter,
+    type_regex: Option<Regex>,
+}
+
+#[derive(Debug)]
+struct EntersTappedRule {
+    filter: EntersTappedFilter,
+    type_regex: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorIdentityMode {
+    #[default]
+    Exact,
+    Subset,
+    Superset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ManaMode {
+    All,
+    Any,
+}
+
+/// The color combinations a cycle of cards covers, one card each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CycleKind {
+    Dual,
+    Tri,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TagSource {
+    Manual,
+    Cmc,
+    ColorIdentity,
+    Mana,
+    ManaAbility,
+    EntersTapped,
+    TypeLine,
+    Format,
+    Implied,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CmcRange {
+    min: Option<f32>,
+    max: Option<f32>,
 }
 
 #[derive(Debug)]
 pub struct TagRef<'a>(&'a TagData);
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Every page name a tag has been published under, mapped to the tag name it
+/// was last used for, so that renamed tags keep their old links working.
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(transparent)]
-struct TagConfigFile(HashMap<String, TagConfig>);
+pub struct SlugHistory(BTreeMap<String, String>);
+
+type ConfigTable = toml::map::Map<String, toml::Value>;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TagConfigFile {
+    #[serde(default)]
+    kinds: HashMap<TagKind, KindConfig>,
+    #[serde(flatten)]
+    tags: HashMap<String, TagConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct KindConfig {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    class: Option<String>,
+    #[serde(default)]
+    sort: Option<KindSort>,
+    #[serde(default)]
+    position: Option<i32>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -70,14 +185,30 @@ struct TagConfig {
     #[serde(default)]
     color_identity: Option<Vec<Color>>,
     #[serde(default)]
-    mana: Option<Vec<Color>>,
+    color_identity_mode: ColorIdentityMode,
+    #[serde(default)]
+    mana: Option<Vec<ManaSymbol>>,
+    #[serde(default)]
+    mana_mode: Option<ManaMode>,
+    #[serde(default)]
+    mana_ability: Option<ManaAbilityConfig>,
+    #[serde(default)]
+    enters_tapped: Option<EntersTappedConfig>,
+    #[serde(default)]
+    cycle: Option<CycleKind>,
     #[serde(default)]
     cmc: Option<f32>,
     #[serde(default)]
+    cmc_min: Option<f32>,
+    #[serde(default)]
+    cmc_max: Option<f32>,
+    #[serde(default)]
     alt_names: Vec<String>,
     #[serde(default)]
     subtags: Vec<String>,
     #[serde(default)]
+    implies: Vec<String>,
+    #[serde(default)]
     kind: TagKind,
     #[serde(default)]
     format: Option<Format>,
@@ -85,156 +216,454 @@ struct TagConfig {
     legality: Option<Legality>,
     #[serde(default)]
     description: Option<String>,
+    #[serde(default)]
+    include: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManaAbilityConfig {
+    #[serde(flatten)]
+    filter: ManaAbilityFilter,
+    #[serde(default, rename = "type")]
+    type_regex: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum EntersTappedConfig {
+    Guarded {
+        #[serde(flatten)]
+        filter: EntersTappedFilter,
+        #[serde(rename = "type")]
+        type_regex: String,
+    },
+    Filter(EntersTappedFilter),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct CardTags(HashMap<String, Vec<String>>);
+pub struct CardTags(HashMap<String, CardTagEntry>);
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum CardTagEntry {
+    Tags(Vec<String>),
+    Config(CardTagConfig),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CardTagConfig {
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    set: Option<String>,
+}
 
 impl TagKind {
-    pub fn class(self) -> &'static str {
+    const BUILT_IN: [TagKind; 6] = [
+        TagKind::ColorIdentity,
+        TagKind::ManaPool,
+        TagKind::Cost,
+        TagKind::TypeLine,
+        TagKind::Format,
+        TagKind::Other,
+    ];
+
+    pub fn id(&self) -> &str {
         match self {
-            TagKind::Other => "badge-dark",
-            TagKind::ColorIdentity => "badge-warning",
-            TagKind::ManaPool => "badge-success",
-            TagKind::TypeLine => "badge-info",
-            TagKind::Cost => "badge-secondary",
-            TagKind::Format => "badge-danger",
+            TagKind::ColorIdentity => "color-identity",
+            TagKind::ManaPool => "mana-pool",
+            TagKind::Cost => "cost",
+            TagKind::TypeLine => "type",
+            TagKind::Format => "format",
+            TagKind::Other => "other",
+            TagKind::Custom(id) => id,
         }
     }
 
-    pub fn sort_tags(self, tags: &mut Vec<TagRef<'_>>) {
-        match self {
-            TagKind::ColorIdentity => tags.sort_unstable_by_key(|tag| tag.color_identity),
-            TagKind::ManaPool => tags.sort_unstable_by_key(|tag| tag.mana),
-            TagKind::Cost => tags.sort_unstable_by_key(|tag| tag.cmc.map(|m| m as i32)),
-            _ => tags.sort_unstable_by_key(|tag| tag.name.clone()),
+    fn default_data(&self) -> KindData {
+        let (name, class, sort, position) = match self {
+            TagKind::ColorIdentity => (
+                "Color Identity",
+                "badge-warning",
+                KindSort::ColorIdentity,
+                10,
+            ),
+            TagKind::ManaPool => ("Mana", "badge-success", KindSort::Mana, 20),
+            TagKind::Cost => ("Converted Mana Cost", "badge-secondary", KindSort::Cmc, 30),
+            TagKind::TypeLine => ("Type", "badge-info", KindSort::Name, 40),
+            TagKind::Format => ("Format Legality", "badge-danger", KindSort::Name, 50),
+            TagKind::Other => ("Other", "badge-dark", KindSort::Name, 100),
+            TagKind::Custom(id) => (id.as_str(), "badge-dark", KindSort::Name, 90),
+        };
+        KindData {
+            kind: self.clone(),
+            name: name.to_owned(),
+            class: class.to_owned(),
+            sort,
+            position,
+        }
+    }
+}
+
+impl From<String> for TagKind {
+    fn from(id: String) -> TagKind {
+        match TagKind::BUILT_IN.iter().find(|kind| kind.id() == id) {
+            Some(kind) => kind.clone(),
+            None => TagKind::Custom(id),
         }
     }
 }
 
+impl From<TagKind> for String {
+    fn from(kind: TagKind) -> String {
+        kind.id().to_owned()
+    }
+}
+
 impl std::fmt::Display for TagKind {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            fmt,
-            "{}",
-            match self {
-                TagKind::ColorIdentity => "Color Identity",
-                TagKind::Cost => "Converted Mana Cost",
-                TagKind::ManaPool => "Mana",
-                TagKind::TypeLine => "Type",
-                TagKind::Format => "Format Legality",
-                TagKind::Other => "Other",
-            }
-        )
+        write!(fmt, "{}", self.id())
+    }
+}
+
+impl KindData {
+    fn with_config(mut self, config: KindConfig) -> KindData {
+        self.name = config.name.unwrap_or(self.name);
+        self.class = config.class.unwrap_or(self.class);
+        self.sort = config.sort.unwrap_or(self.sort);
+        self.position = config.position.unwrap_or(self.position);
+        self
+    }
+
+    pub fn kind(&self) -> &TagKind {
+        &self.kind
+    }
+
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    pub fn sort_tags(&self, tags: &mut [TagRef<'_>]) {
+        match self.sort {
+            KindSort::ColorIdentity => tags.sort_unstable_by_key(|tag| tag.color_identity),
+            KindSort::Mana => tags.sort_unstable_by_key(|tag| tag.mana),
+            KindSort::Cmc => tags.sort_unstable_by(|a, b| {
+                let (a, b) = (a.cmc.unwrap_or_default(), b.cmc.unwrap_or_default());
+                a.min()
+                    .total_cmp(&b.min())
+                    .then(a.max().total_cmp(&b.max()))
+            }),
+            KindSort::Name => tags.sort_unstable_by_key(|tag| tag.name.clone()),
+        }
+    }
+}
+
+impl std::fmt::Display for KindData {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(fmt, "{}", self.name)
     }
 }
 
 impl<'a> TagDb<'a> {
     pub fn new(index: &'a TagIndex) -> TagDb<'a> {
-        let mut kind_index: BTreeMap<TagKind, Vec<TagRef<'a>>> = BTreeMap::new();
+        let mut kind_map: HashMap<&TagKind, (&'a KindData, Vec<TagRef<'a>>)> = HashMap::new();
         for (_, tag) in index.iter() {
-            if !kind_index.get(&tag.kind).is_some() {
-                kind_index.insert(tag.kind, Vec::new());
-            }
-            let taglist = kind_index.get_mut(&tag.kind).unwrap();
-            taglist.push(tag);
+            kind_map
+                .entry(&tag.0.kind.kind)
+                .or_insert_with(|| (&tag.0.kind, vec![]))
+                .1
+                .push(tag);
         }
+        let mut kind_index: Vec<_> = kind_map.into_values().collect();
+        kind_index.sort_unstable_by(|(a, _), (b, _)| {
+            a.position
+                .cmp(&b.position)
+                .then_with(|| a.name.cmp(&b.name))
+        });
         for (kind, taglist) in kind_index.iter_mut() {
             kind.sort_tags(taglist);
         }
-        TagDb { kind_index }
+
+        let mut parent_index: HashMap<TagRef<'a>, Vec<TagRef<'a>>> = HashMap::new();
+        for (_, tag) in index.iter() {
+            for subtag in tag.subtags().iter().filter_map(|name| index.get(name)) {
+                parent_index.entry(subtag).or_default().push(tag);
+            }
+        }
+        for parents in parent_index.values_mut() {
+            parents.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        for cycle in index.subtag_cycles() {
+            warn!(
+                "subtag cycle: {}",
+                join(cycle.iter().map(|tag| &tag.name), " -> ")
+            );
+        }
+
+        TagDb {
+            kind_index,
+            parent_index,
+        }
     }
 
-    pub fn kind_index(&self) -> &BTreeMap<TagKind, Vec<TagRef<'_>>> {
+    pub fn kind_index(&self) -> &[(&'a KindData, Vec<TagRef<'a>>)] {
         &self.kind_index
     }
+
+    pub fn parents(&self, tag: &TagRef<'a>) -> &[TagRef<'a>] {
+        self.parent_index
+            .get(tag)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
 }
 
 impl TagIndex {
-    pub fn load(config_file: &Path) -> anyhow::Result<TagIndex> {
-        debug!("loading tags config file");
-        let config = toml::from_str(&std::fs::read_to_string(config_file)?)?;
+    pub fn load(config_path: &Path) -> anyhow::Result<TagIndex> {
+        debug!("loading tags config");
+        let config = load_config(config_path, &["kinds"])?;
         debug!("indexing tags");
-        Ok(TagIndex::from_config(config)?)
+        TagIndex::from_config(config)
     }
 
-    fn from_config(config: TagConfigFile) -> Result<Self, regex::Error> {
+    fn from_config(mut config: TagConfigFile) -> anyhow::Result<Self> {
+        let mut kinds: HashMap<_, _> = HashMap::new();
+        for kind in TagKind::BUILT_IN {
+            let data = match config.kinds.remove(&kind) {
+                Some(kind_config) => kind.default_data().with_config(kind_config),
+                None => kind.default_data(),
+            };
+            kinds.insert(kind, Arc::new(data));
+        }
+        for (kind, kind_config) in config.kinds {
+            kinds.insert(
+                kind.clone(),
+                Arc::new(kind.default_data().with_config(kind_config)),
+            );
+        }
+
         let mut tags: HashMap<_, _> = HashMap::new();
-        for (name, tag_config) in config.0 {
+        for (name, tag_config) in config.tags {
+            let kind = match kinds.get(&tag_config.kind) {
+                Some(kind) => kind.clone(),
+                None => anyhow::bail!("tag \"{}\" has unknown kind \"{}\"", name, tag_config.kind),
+            };
             tags.insert(
-                name.clone().into(),
-                TagData::from_config(&name, tag_config)?,
+                name.clone(),
+                TagData::from_config(&name, kind, tag_config)
+                    .with_context(|| format!("invalid tag \"{}\"", name))?,
             );
         }
 
-        Ok(TagIndex(tags))
+        let mut aliases = HashMap::new();
+        for (name, tag) in tags.iter() {
+            for alias in tag.alt_names.iter() {
+                if tags.contains_key(alias) {
+                    anyhow::bail!(
+                        "alt-name \"{}\" of tag \"{}\" is also a tag name",
+                        alias,
+                        name
+                    );
+                }
+                if let Some(other) = aliases.insert(alias.clone(), name.clone()) {
+                    let (first, second) = (other.as_str().min(name), other.as_str().max(name));
+                    anyhow::bail!(
+                        "alt-name \"{}\" is used by both \"{}\" and \"{}\"",
+                        alias,
+                        first,
+                        second
+                    );
+                }
+            }
+        }
+
+        let index = TagIndex {
+            tags,
+            kinds,
+            aliases,
+        };
+        index.check_slugs()?;
+        Ok(index)
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&str, TagRef<'_>)> {
-        self.0.iter().map(|(s, d)| (s.as_ref(), TagRef::new(d)))
+        self.tags.iter().map(|(s, d)| (s.as_ref(), TagRef::new(d)))
     }
 
+    /// Looks up a tag by its name or one of its alt-names.
     pub fn get(&self, tag: &str) -> Option<TagRef<'_>> {
-        self.0.get(tag).map(TagRef::new)
+        self.tags
+            .get(tag)
+            .or_else(|| self.tags.get(self.resolve_alias(tag)?))
+            .map(TagRef::new)
     }
 
-    pub fn merge_tags(&mut self, card_tags: &CardTags) {
+    /// Returns the tag name an alt-name refers to.
+    pub fn resolve_alias(&self, alias: &str) -> Option<&str> {
+        self.aliases.get(alias).map(String::as_str)
+    }
+
+    /// Finds cycles in the subtag graph, each returned as the path of tags
+    /// leading from a tag back to itself. Two tags listing each other are
+    /// related on purpose and not a cycle.
+    pub fn subtag_cycles(&self) -> Vec<Vec<TagRef<'_>>> {
+        let mut tags: Vec<_> = self.iter().map(|(_, tag)| tag).collect();
+        tags.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        let mut visited = HashSet::new();
+        let mut path = Vec::new();
+        let mut cycles = Vec::new();
+        for tag in tags {
+            self.find_cycles(tag, &mut visited, &mut path, &mut cycles);
+        }
+        cycles
+    }
+
+    fn find_cycles<'a>(
+        &'a self,
+        tag: TagRef<'a>,
+        visited: &mut HashSet<TagRef<'a>>,
+        path: &mut Vec<TagRef<'a>>,
+        cycles: &mut Vec<Vec<TagRef<'a>>>,
+    ) {
+        if let Some(start) = path.iter().position(|t| *t == tag) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(tag);
+            cycles.push(cycle);
+            return;
+        }
+        if !visited.insert(tag) {
+            return;
+        }
+        path.push(tag);
+        for subtag in tag.subtags().iter().filter_map(|name| self.get(name)) {
+            if !self.lists_subtag(subtag, tag) {
+                self.find_cycles(subtag, visited, path, cycles);
+            }
+        }
+        path.pop();
+    }
+
+    fn lists_subtag(&self, tag: TagRef<'_>, subtag: TagRef<'_>) -> bool {
+        tag.subtags()
+            .iter()
+            .any(|name| self.get(name) == Some(subtag))
+    }
+
+    pub fn merge_tags(&mut self, card_tags: &CardTags) -> anyhow::Result<()> {
         for tag in card_tags.tags() {
-            if !self.0.contains_key(tag) {
-                self.0.insert(tag.to_owned(), TagData::new(tag));
+            if self.get(tag).is_none() {
+                let kind = self.kinds[&TagKind::Other].clone();
+                self.tags.insert(tag.to_owned(), TagData::new(tag, kind));
+            }
+        }
+        self.check_slugs()
+    }
+
+    fn check_slugs(&self) -> anyhow::Result<()> {
+        let mut slugs: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (name, tag) in self.tags.iter() {
+            slugs.entry(&tag.slug).or_default().push(name);
+        }
+        for (slug, mut names) in slugs {
+            if names.len() > 1 {
+                names.sort_unstable();
+                anyhow::bail!(
+                    "tags {} share the page name \"{}\"",
+                    join(names.iter().map(|name| format!("\"{}\"", name)), ", "),
+                    slug
+                );
             }
         }
+        Ok(())
     }
 }
 
 impl TagData {
-    fn new(name: &str) -> TagData {
+    fn new(name: &str, kind: Arc<KindData>) -> TagData {
         TagData {
             name: name.to_owned(),
             alt_names: Default::default(),
             subtags: Default::default(),
-            canonical_name: TAG_NAME_STRIP_REGEX.replace_all(name, "_").to_string(),
-            kind: TagKind::Other,
+            implies: Default::default(),
+            slug: slugify(name),
+            kind,
             color_identity: None,
+            color_identity_mode: ColorIdentityMode::Exact,
             cmc: None,
             type_regex: None,
             mana: None,
+            mana_mode: None,
+            mana_ability: None,
+            enters_tapped: None,
+            cycle: None,
             format: None,
             description: None,
+            include: false,
         }
     }
 
-    fn from_config(name: &str, config: TagConfig) -> Result<TagData, regex::Error> {
+    fn from_config(name: &str, kind: Arc<KindData>, config: TagConfig) -> anyhow::Result<TagData> {
         Ok(TagData {
             name: name.to_owned(),
             alt_names: config.alt_names.into_iter().collect(),
             subtags: config.subtags.into_iter().collect(),
-            canonical_name: TAG_NAME_STRIP_REGEX.replace_all(name, "_").to_string(),
-            kind: config.kind,
+            implies: config.implies.into_iter().collect(),
+            slug: slugify(name),
+            kind,
             color_identity: config.color_identity.clone().map(Colors::from_vec),
-            cmc: config.cmc,
-            type_regex: config
-                .type_regex
-                .as_ref()
-                .map(|s| {
-                    let mut builder = RegexBuilder::new(&s);
-                    builder.dot_matches_new_line(true);
-                    builder.build()
+            color_identity_mode: config.color_identity_mode,
+            cmc: CmcRange::new(config.cmc, config.cmc_min, config.cmc_max),
+            type_regex: config.type_regex.as_deref().map(type_regex).transpose()?,
+            mana: config
+                .mana
+                .as_deref()
+                .map(Mana::from_symbols)
+                .transpose()
+                .map_err(anyhow::Error::msg)?,
+            mana_mode: config.mana_mode,
+            mana_ability: config
+                .mana_ability
+                .map(|rule| {
+                    Ok::<_, regex::Error>(ManaAbilityRule {
+                        filter: rule.filter,
+                        type_regex: rule.type_regex.as_deref().map(type_regex).transpose()?,
+                    })
                 })
                 .transpose()?,
-            mana: config.mana.clone().map(Colors::from_vec),
+            enters_tapped: config
+                .enters_tapped
+                .map(|rule| {
+                    Ok::<_, regex::Error>(match rule {
+                        EntersTappedConfig::Guarded {
+                            filter,
+                            type_regex: t,
+                        } => EntersTappedRule {
+                            filter,
+                            type_regex: Some(type_regex(&t)?),
+                        },
+                        EntersTappedConfig::Filter(filter) => EntersTappedRule {
+                            filter,
+                            type_regex: None,
+                        },
+                    })
+                })
+                .transpose()?,
+            cycle: config.cycle,
             format: match (config.format, config.legality) {
                 (Some(f), Some(l)) => Some((f, l)),
                 _ => None,
             },
             description: config.description,
+            include: config.include,
         })
     }
 
     pub fn name(&self) -> Cow<'_, str> {
-        match self.kind {
-            TagKind::Cost => Cow::Owned(format!("CMC: {}", self.cmc.unwrap_or_default() as i32)),
+        match self.kind.kind {
+            TagKind::Cost => Cow::Owned(format!("CMC: {}", self.cmc.unwrap_or_default())),
             TagKind::Format => Cow::Owned(format!(
                 "{} in {}",
                 self.format.unwrap().1,
@@ -244,8 +673,8 @@ impl TagData {
         }
     }
 
-    pub fn kind(&self) -> TagKind {
-        self.kind
+    pub fn kind(&self) -> &KindData {
+        &self.kind
     }
 
     pub fn color_identity_symbols(&self) -> Cow<'static, str> {
@@ -256,6 +685,16 @@ impl TagData {
         }
     }
 
+    /// The mana a mana pool tag's cards produce, if the tag has a `mana` rule.
+    pub fn mana(&self) -> Option<Mana> {
+        self.mana
+    }
+
+    /// The color combinations the tag's cards should cover, if it's marked as a cycle.
+    pub fn cycle(&self) -> Option<CycleKind> {
+        self.cycle
+    }
+
     pub fn mana_symbols(&self) -> Cow<'static, str> {
         if let Some(mana) = &self.mana {
             mana.mana_symbols()
@@ -266,20 +705,32 @@ impl TagData {
 
     pub fn cmc_symbol(&self) -> Cow<'static, str> {
         if let Some(cmc) = &self.cmc {
-            Cow::Owned(format!("<span class=\"mana s{}\"></span>", *cmc as i32))
+            Cow::Owned(cmc.mana_symbols())
         } else {
             Cow::Borrowed("")
         }
     }
 
-    pub fn canonical_name(&self) -> &str {
-        &self.canonical_name
+    pub fn slug(&self) -> &str {
+        &self.slug
     }
 
     pub fn subtags(&self) -> &BTreeSet<String> {
         &self.subtags
     }
 
+    /// The tags every card with this tag also gets.
+    pub fn implies(&self) -> &BTreeSet<String> {
+        &self.implies
+    }
+
+    /// Whether this tag implies another, named by its tag name or one of its alt-names.
+    pub fn implies_tag(&self, tag: &TagData) -> bool {
+        self.implies
+            .iter()
+            .any(|name| *name == tag.name || tag.alt_names.contains(name))
+    }
+
     pub fn has_alt_names(&self) -> bool {
         !self.alt_names.is_empty()
     }
@@ -288,22 +739,70 @@ impl TagData {
         join(self.alt_names.iter(), ", ")
     }
 
-    pub fn is_match(&self, card: &Card, type_line: &str) -> bool {
+    /// Returns every rule of the tag that matches a card, so a card matched by more than one is
+    /// explained by each of them.
+    pub fn match_rules(&self, card: &Card, traits: &CardTraits) -> Vec<TagSource> {
+        let mut sources = Vec::new();
+        let type_line = traits.type_line();
         if let Some(cmc) = self.cmc {
-            if card.cmc == cmc && (!type_line.contains("Land") || cmc > 0.0) {
-                return true;
+            if cmc.contains(card.cmc) && (!type_line.contains("Land") || card.cmc > 0.0) {
+                sources.push(TagSource::Cmc);
             }
         }
 
-        if let Some(color_identity) = &self.color_identity {
-            if &Colors::from_vec(card.color_identity.clone()) == color_identity {
-                return true;
+        if let Some(color_identity) = self.color_identity {
+            let card_identity = Colors::from_vec(card.color_identity.clone());
+            if match self.color_identity_mode {
+                ColorIdentityMode::Exact => card_identity == color_identity,
+                ColorIdentityMode::Subset => card_identity.is_subset(color_identity),
+                ColorIdentityMode::Superset => card_identity.is_superset(color_identity),
+            } {
+                sources.push(TagSource::ColorIdentity);
+            }
+        }
+
+        if let (Some(mana), Some(mana_mode)) = (self.mana, self.mana_mode) {
+            let produced = card.produced_colors();
+            if match (mana, mana_mode) {
+                (Mana::Colorless, _) => card.produces_colorless(),
+                (Mana::Colors(colors), ManaMode::All) => {
+                    colors.into_vec().iter().all(|c| produced.contains(c))
+                }
+                (Mana::Colors(colors), ManaMode::Any) => {
+                    colors.into_vec().iter().any(|c| produced.contains(c))
+                }
+                (Mana::AnyColor, _) => traits
+                    .mana_abilities()
+                    .iter()
+                    .any(|ability| ability.any_color() && !ability.commander_color()),
+                (Mana::CommanderColor, _) => traits
+                    .mana_abilities()
+                    .iter()
+                    .any(|ability| ability.commander_color()),
+                (Mana::Snow, _) => {
+                    type_line.contains("Snow")
+                        && (!produced.is_empty() || card.produces_colorless())
+                }
+            } {
+                sources.push(TagSource::Mana);
+            }
+        }
+
+        if let Some(rule) = &self.mana_ability {
+            if rule.matches(traits).is_some() {
+                sources.push(TagSource::ManaAbility);
+            }
+        }
+
+        if let Some(rule) = &self.enters_tapped {
+            if rule.matches(traits) {
+                sources.push(TagSource::EntersTapped);
             }
         }
 
         if let Some(type_regex) = &self.type_regex {
             if type_regex.is_match(type_line.as_ref()) {
-                return true;
+                sources.push(TagSource::TypeLine);
             }
         }
 
@@ -315,27 +814,69 @@ impl TagData {
                 .filter(|l| l == &legality)
                 .is_some()
             {
-                return true;
+                sources.push(TagSource::Format);
             }
         }
 
-        false
+        sources
+    }
+
+    pub fn is_match(&self, card: &Card, traits: &CardTraits) -> bool {
+        !self.match_rules(card, traits).is_empty()
+    }
+
+    pub fn explain(&self, source: TagSource, card: &Card, traits: &CardTraits) -> String {
+        match source {
+            TagSource::Manual => "listed in card-tags.toml".to_owned(),
+            TagSource::Cmc => format!("{} is {}", card.cmc, self.cmc.unwrap_or_default()),
+            TagSource::ColorIdentity => format!(
+                "{} is {} {}",
+                Colors::from_vec(card.color_identity.clone()).name(),
+                self.color_identity_mode,
+                self.color_identity.map(Colors::name).unwrap_or_default()
+            ),
+            TagSource::Mana => format!(
+                "produces {} of {}",
+                self.mana_mode.map(ManaMode::name).unwrap_or_default(),
+                self.mana.map(Mana::name).unwrap_or_default()
+            ),
+            TagSource::ManaAbility => match &self.mana_ability {
+                Some(rule) => format!(
+                    "'{}' {}",
+                    rule.matches(traits).map(|a| a.text()).unwrap_or_default(),
+                    rule.filter
+                ),
+                None => String::new(),
+            },
+            TagSource::EntersTapped => traits.enters_tapped().to_string(),
+            TagSource::TypeLine => format!(
+                "matches '{}'",
+                self.type_regex
+                    .as_ref()
+                    .map(Regex::as_str)
+                    .unwrap_or_default()
+            ),
+            TagSource::Format => match self.format {
+                Some((format, legality)) => format!("{} in {}", legality, format),
+                None => String::new(),
+            },
+            // Only the card knows which of its tags imply this one.
+            TagSource::Implied => String::new(),
+        }
     }
 
     pub fn description(&self) -> Option<&str> {
         self.description.as_deref()
     }
-}
 
-impl Default for TagKind {
-    fn default() -> Self {
-        TagKind::Other
+    pub fn is_included(&self) -> bool {
+        self.include
     }
 }
 
 impl std::fmt::Display for TagData {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self.kind {
+        match self.kind.kind {
             TagKind::ColorIdentity => write!(
                 fmt,
                 "Color Identity: {} {}",
@@ -358,6 +899,153 @@ impl std::fmt::Display for TagData {
     }
 }
 
+impl CycleKind {
+    pub fn combinations(self) -> Vec<Colors> {
+        match self {
+            CycleKind::Dual => DualColor::ALL.map(Colors::Dual).to_vec(),
+            CycleKind::Tri => TriColor::ALL.map(Colors::Tri).to_vec(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CycleKind::Dual => "Dual",
+            CycleKind::Tri => "Tricolor",
+        }
+    }
+
+    pub fn slug(self) -> &'static str {
+        match self {
+            CycleKind::Dual => "dual",
+            CycleKind::Tri => "tri",
+        }
+    }
+}
+
+impl ManaMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ManaMode::All => "all",
+            ManaMode::Any => "any",
+        }
+    }
+}
+
+impl std::fmt::Display for ColorIdentityMode {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{}",
+            match self {
+                ColorIdentityMode::Exact => "exactly",
+                ColorIdentityMode::Subset => "within",
+                ColorIdentityMode::Superset => "including",
+            }
+        )
+    }
+}
+
+impl std::fmt::Display for TagSource {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{}",
+            match self {
+                TagSource::Manual => "manual",
+                TagSource::Cmc => "cmc",
+                TagSource::ColorIdentity => "color identity",
+                TagSource::Mana => "mana",
+                TagSource::ManaAbility => "mana ability",
+                TagSource::EntersTapped => "enters tapped",
+                TagSource::TypeLine => "type regex",
+                TagSource::Format => "format",
+                TagSource::Implied => "implied",
+            }
+        )
+    }
+}
+
+impl ManaAbilityRule {
+    fn matches<'t>(&self, traits: &'t CardTraits) -> Option<&'t ManaAbility> {
+        if let Some(type_regex) = &self.type_regex {
+            if !type_regex.is_match(traits.type_line()) {
+                return None;
+            }
+        }
+        traits
+            .mana_abilities()
+            .iter()
+            .find(|ability| self.filter.matches(ability))
+    }
+}
+
+impl EntersTappedRule {
+    fn matches(&self, traits: &CardTraits) -> bool {
+        self.type_regex
+            .as_ref()
+            .is_none_or(|type_regex| type_regex.is_match(traits.type_line()))
+            && self.filter.matches(traits.enters_tapped())
+    }
+}
+
+impl CmcRange {
+    fn new(cmc: Option<f32>, min: Option<f32>, max: Option<f32>) -> Option<CmcRange> {
+        let range = CmcRange {
+            min: min.or(cmc),
+            max: max.or(cmc),
+        };
+        if range.min.is_none() && range.max.is_none() {
+            None
+        } else {
+            Some(range)
+        }
+    }
+
+    pub fn min(self) -> f32 {
+        self.min.unwrap_or(f32::NEG_INFINITY)
+    }
+
+    pub fn max(self) -> f32 {
+        self.max.unwrap_or(f32::INFINITY)
+    }
+
+    pub fn contains(self, cmc: f32) -> bool {
+        self.min() <= cmc && cmc <= self.max()
+    }
+
+    pub fn mana_symbols(self) -> String {
+        let symbol = |cmc: f32| format!("<span class=\"mana s{}\"></span>", cmc as i32);
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => symbol(min),
+            (Some(min), Some(max)) => format!("{}–{}", symbol(min), symbol(max)),
+            (Some(min), None) => format!("{}+", symbol(min)),
+            (None, Some(max)) => format!("≤{}", symbol(max)),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl Default for CmcRange {
+    fn default() -> Self {
+        CmcRange {
+            min: Some(0.0),
+            max: Some(0.0),
+        }
+    }
+}
+
+impl std::fmt::Display for CmcRange {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) if min == max => write!(fmt, "{}", min),
+            (Some(min), Some(max)) => write!(fmt, "{}–{}", min, max),
+            (Some(min), None) => write!(fmt, "{}+", min),
+            (None, Some(max)) => write!(fmt, "≤{}", max),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
 impl<'a> TagRef<'a> {
     pub fn new(tag: &'a TagData) -> TagRef<'a> {
         TagRef(tag)
@@ -366,7 +1054,7 @@ impl<'a> TagRef<'a> {
 
 impl<'a> Clone for TagRef<'a> {
     fn clone(&self) -> TagRef<'a> {
-        TagRef(self.0)
+        *self
     }
 }
 
@@ -406,9 +1094,26 @@ impl<'a> std::fmt::Display for TagRef<'a> {
 }
 
 impl CardTags {
-    pub fn load(config_file: &Path) -> anyhow::Result<CardTags> {
+    pub fn load(config_path: &Path) -> anyhow::Result<CardTags> {
         debug!("loading card tag list");
-        Ok(toml::from_str(&std::fs::read_to_string(config_file)?)?)
+        load_config(config_path, &[])
+    }
+
+    /// Rewrites tags given by an alt-name to their tag name in every card tag
+    /// file, returning the number of tags rewritten.
+    pub fn rewrite_aliases(config_path: &Path, tag_index: &TagIndex) -> anyhow::Result<usize> {
+        let mut count = 0;
+        for file in config_files(config_path)? {
+            let text = std::fs::read_to_string(&file)?;
+            let (rewritten, file_count) = rewrite_aliases(&text, tag_index)
+                .with_context(|| format!("failed to parse {}", file.display()))?;
+            if file_count > 0 {
+                info!("rewrote {} alt-names in {}", file_count, file.display());
+                std::fs::write(&file, rewritten)?;
+                count += file_count;
+            }
+        }
+        Ok(count)
     }
 
     pub fn cards(&self) -> impl Iterator<Item = &str> {
@@ -416,10 +1121,750 @@ impl CardTags {
     }
 
     fn tags(&self) -> HashSet<&str> {
-        self.0.values().flatten().map(|s| s.as_ref()).collect()
+        self.0.values().flat_map(CardTagEntry::included).collect()
+    }
+
+    pub fn get_tags(&self, name: &str) -> Option<Vec<&str>> {
+        self.0.get(name).map(|entry| entry.included().collect())
     }
 
-    pub fn get_tags(&self, name: &str) -> Option<&Vec<String>> {
-        self.0.get(name)
+    pub fn get_excluded_tags(&self, name: &str) -> Vec<&str> {
+        self.0
+            .get(name)
+            .map(|entry| entry.excluded().collect())
+            .unwrap_or_default()
+    }
+
+    /// Lists the cards whose entry excludes a tag that isn't a tag name or alt-name, with that
+    /// tag, sorted by card name.
+    pub fn unknown_exclusions(&self, tag_index: &TagIndex) -> Vec<(&str, &str)> {
+        let mut unknown: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|(name, entry)| entry.excluded().map(move |tag| (name.as_str(), tag)))
+            .filter(|(_, tag)| tag_index.get(tag).is_none())
+            .collect();
+        unknown.sort_unstable();
+        unknown
+    }
+
+    pub fn get_set(&self, name: &str) -> Option<&str> {
+        match self.0.get(name) {
+            Some(CardTagEntry::Config(config)) => config.set.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl CardTagEntry {
+    fn tags(&self) -> &Vec<String> {
+        match self {
+            CardTagEntry::Tags(tags) => tags,
+            CardTagEntry::Config(config) => &config.tags,
+        }
+    }
+
+    fn included(&self) -> impl Iterator<Item = &str> {
+        self.tags()
+            .iter()
+            .map(String::as_str)
+            .filter(|tag| !tag.starts_with('-'))
+    }
+
+    // Tags written as "-Tag Name" remove a tag the card would otherwise get from a rule.
+    fn excluded(&self) -> impl Iterator<Item = &str> {
+        self.tags().iter().filter_map(|tag| tag.strip_prefix('-'))
+    }
+}
+
+/// Rewrites the alt-names in the tag lists of a card tag file, keeping its formatting, and returns
+/// the new text with the number of tags rewritten. Card names and other keys are left alone.
+fn rewrite_aliases(text: &str, tag_index: &TagIndex) -> anyhow::Result<(String, usize)> {
+    let mut document: toml_edit::Document = text.parse()?;
+    let mut count = 0;
+    for (key, item) in document.iter_mut() {
+        if key.get() == "include" {
+            continue;
+        }
+        let tags = if item.is_table_like() {
+            item.get_mut("tags")
+        } else {
+            Some(item)
+        };
+        for tag in tags
+            .and_then(toml_edit::Item::as_array_mut)
+            .into_iter()
+            .flat_map(toml_edit::Array::iter_mut)
+        {
+            let (prefix, name) = match tag.as_str() {
+                Some(name) => match name.strip_prefix('-') {
+                    Some(name) => ("-", name),
+                    None => ("", name),
+                },
+                None => continue,
+            };
+            if let Some(resolved) = tag_index.resolve_alias(name) {
+                let decor = tag.decor().clone();
+                *tag = format!("{}{}", prefix, resolved).into();
+                *tag.decor_mut() = decor;
+                count += 1;
+            }
+        }
+    }
+    // toml_edit writes plain newlines, so keep files that use CRLF that way.
+    let rewritten = document.to_string();
+    if text.contains("\r\n") {
+        Ok((rewritten.replace("\r\n", "\n").replace('\n', "\r\n"), count))
+    } else {
+        Ok((rewritten, count))
+    }
+}
+
+/// Loads a TOML config from a file or a directory of `*.toml` files, following
+/// any top-level `include = [...]` lists relative to the including file. Files
+/// are merged in path order and a key defined in more than one file is an
+/// error, except inside the tables named in `nested`, which are merged by key.
+fn load_config<T: DeserializeOwned>(path: &Path, nested: &[&str]) -> anyhow::Result<T> {
+    let mut merged = ConfigTable::new();
+    let mut sources = HashMap::new();
+    load_config_tables(path, nested, &mut merged, &mut sources, &mut vec![])?;
+    Ok(toml::Value::Table(merged).try_into()?)
+}
+
+/// Lists the files a config is loaded from, following includes.
+fn config_files(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut loaded = vec![];
+    load_config_tables(
+        path,
+        &[],
+        &mut ConfigTable::new(),
+        &mut HashMap::new(),
+        &mut loaded,
+    )?;
+    Ok(loaded)
+}
+
+fn load_config_tables(
+    path: &Path,
+    nested: &[&str],
+    merged: &mut ConfigTable,
+    sources: &mut HashMap<String, PathBuf>,
+    loaded: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    if path.is_dir() {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|ext| ext == "toml") {
+                files.push(file);
+            }
+        }
+        files.sort();
+        for file in files {
+            load_config_tables(&file, nested, merged, sources, loaded)?;
+        }
+        return Ok(());
+    }
+
+    let canonical_path = path.canonicalize()?;
+    if loaded.contains(&canonical_path) {
+        anyhow::bail!("{} is included more than once", path.display());
+    }
+    loaded.push(canonical_path);
+    debug!("loading config file {}", path.display());
+    let mut table: ConfigTable = toml::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err))?;
+    let includes: Vec<String> = match table.remove("include") {
+        Some(includes) => includes.try_into()?,
+        None => vec![],
+    };
+
+    for (key, value) in table {
+        match (merged.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(values))
+                if nested.contains(&key.as_str()) =>
+            {
+                for (subkey, value) in values {
+                    let source_key = format!("{}.{}", key, subkey);
+                    check_duplicate_key(&source_key, path, sources)?;
+                    existing.insert(subkey, value);
+                }
+            }
+            (_, value) => {
+                check_duplicate_key(&key, path, sources)?;
+                if let toml::Value::Table(values) = &value {
+                    if nested.contains(&key.as_str()) {
+                        for subkey in values.keys() {
+                            sources.insert(format!("{}.{}", key, subkey), path.to_owned());
+                        }
+                    }
+                }
+                merged.insert(key, value);
+            }
+        }
+    }
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for include in includes {
+        load_config_tables(&base_dir.join(include), nested, merged, sources, loaded)?;
+    }
+    Ok(())
+}
+
+fn check_duplicate_key(
+    key: &str,
+    path: &Path,
+    sources: &mut HashMap<String, PathBuf>,
+) -> anyhow::Result<()> {
+    match sources.insert(key.to_owned(), path.to_owned()) {
+        Some(previous) => anyhow::bail!(
+            "\"{}\" is defined in both {} and {}",
+            key,
+            previous.display(),
+            path.display()
+        ),
+        None => Ok(()),
+    }
+}
+
+impl SlugHistory {
+    pub fn load(history_file: &Path) -> anyhow::Result<SlugHistory> {
+        if !history_file.exists() {
+            return Ok(SlugHistory::default());
+        }
+        debug!("loading slug history");
+        Ok(toml::from_str(&std::fs::read_to_string(history_file)?)?)
+    }
+
+    /// Records the current page name of every tag, returning whether any
+    /// were added or changed.
+    pub fn update(&mut self, tag_index: &TagIndex) -> bool {
+        let mut changed = false;
+        for (name, tag) in tag_index.iter() {
+            if self.0.get(tag.slug()).map(String::as_str) != Some(name) {
+                self.0.insert(tag.slug().to_owned(), name.to_owned());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    pub fn save(&self, history_file: &Path) -> anyhow::Result<()> {
+        std::fs::write(history_file, toml::to_string(&self.0)?)?;
+        Ok(())
+    }
+
+    /// Finds the current tag for each old page name, by the tag name or
+    /// alt-name it was last used for.
+    pub fn redirects<'a>(&self, tag_index: &'a TagIndex) -> Vec<(&str, TagRef<'a>)> {
+        let mut redirects = vec![];
+        for (slug, name) in self.0.iter() {
+            match tag_index.get(name) {
+                Some(tag) if tag.slug() != slug => redirects.push((slug.as_str(), tag)),
+                Some(_) => {}
+                None => warn!(
+                    "tag \"{}\" has been removed, so tag-{}.html is no longer published",
+                    name, slug
+                ),
+            }
+        }
+        redirects
+    }
+}
+
+fn type_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut builder = RegexBuilder::new(pattern);
+    builder.dot_matches_new_line(true);
+    builder.build()
+}
+
+pub fn slugify(name: &str) -> String {
+    TAG_SLUG_STRIP_REGEX
+        .replace_all(&name.to_lowercase(), "-")
+        .trim_matches('-')
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scryfall::CardList;
+
+    fn tag_index(config: &str) -> TagIndex {
+        TagIndex::from_config(toml::from_str(config).unwrap()).unwrap()
+    }
+
+    static SNOW_COVERED_WASTES: &str = r#"[{
+        "id": "00000000-0000-0000-0000-000000000001",
+        "scryfall_uri": "https://scryfall.com/card/csp/155",
+        "cmc": 0.0,
+        "color_identity": [],
+        "name": "Snow-Covered Wastes",
+        "type_line": "Basic Snow Land",
+        "oracle_text": "({T}: Add {C}.)",
+        "produced_mana": ["C"],
+        "set_type": "expansion"
+    }]"#;
+
+    #[test]
+    fn every_matching_rule_is_a_source() {
+        let index = tag_index(
+            r#"
+            ["Snow Mana"]
+            kind = "mana-pool"
+            mana = ["S"]
+            mana-mode = "any"
+            type = 'Snow'
+            color-identity = ["W"]
+            "#,
+        );
+        let cards: CardList = serde_json::from_str(SNOW_COVERED_WASTES).unwrap();
+        let card = &cards.cards()[0];
+        let traits = CardTraits::from_card(card);
+        let tag = index.get("Snow Mana").unwrap();
+        assert_eq!(
+            tag.match_rules(card, &traits),
+            [TagSource::Mana, TagSource::TypeLine]
+        );
+        assert!(tag.is_match(card, &traits));
+    }
+
+    static ALIAS_TAGS: &str = r#"
+        [Fetch]
+        alt-names = ["Land Fetch"]
+
+        ["Sac Land"]
+        alt-names = ["Sacrifice Land"]
+    "#;
+
+    #[test]
+    fn alias_conflicts() {
+        let error = TagIndex::from_config(
+            toml::from_str(
+                r#"
+                [Fetch]
+                alt-names = ["Tutor"]
+
+                [Tutor]
+                "#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "alt-name \"Tutor\" of tag \"Fetch\" is also a tag name"
+        );
+
+        let error = TagIndex::from_config(
+            toml::from_str(
+                r#"
+                [Fetch]
+                alt-names = ["Search"]
+
+                [Tutor]
+                alt-names = ["Search"]
+                "#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "alt-name \"Search\" is used by both \"Fetch\" and \"Tutor\""
+        );
+    }
+
+    #[test]
+    fn aliases_resolve() {
+        let index = tag_index(ALIAS_TAGS);
+        assert_eq!(index.resolve_alias("Land Fetch"), Some("Fetch"));
+        assert_eq!(index.resolve_alias("Fetch"), None);
+        assert_eq!(
+            index.get("Sacrifice Land").unwrap(),
+            index.get("Sac Land").unwrap()
+        );
+    }
+
+    #[test]
+    fn rewrite_aliases_in_tag_lists() {
+        let index = tag_index(ALIAS_TAGS);
+        let text = r#"include = ["Land Fetch"]
+
+# "Land Fetch" in a comment stays.
+"Land Fetch" = ["Land Fetch", "-Sacrifice Land"]
+"Evolving Wilds" = [
+    "Land Fetch",  # trailing comment
+    "Sacrifice Land",
+]
+"Prismatic Vista" = { set = "mh1", tags = ["Sacrifice Land"] }
+
+["Sacrifice Land"]
+tags = ["Fetch", "Land Fetch"]
+"#;
+        let (rewritten, count) = rewrite_aliases(text, &index).unwrap();
+        assert_eq!(count, 6);
+        assert_eq!(
+            rewritten,
+            r#"include = ["Land Fetch"]
+
+# "Land Fetch" in a comment stays.
+"Land Fetch" = ["Fetch", "-Sac Land"]
+"Evolving Wilds" = [
+    "Fetch",  # trailing comment
+    "Sac Land",
+]
+"Prismatic Vista" = { set = "mh1", tags = ["Sac Land"] }
+
+["Sacrifice Land"]
+tags = ["Fetch", "Fetch"]
+"#
+        );
+
+        let crlf = text.replace('\n', "\r\n");
+        let (rewritten, _) = rewrite_aliases(&crlf, &index).unwrap();
+        assert!(!rewritten.replace("\r\n", "").contains('\n'));
+    }
+
+    static CASTLE_VANTRESS: &str = r#"[{
+        "id": "00000000-0000-0000-0000-000000000002",
+        "scryfall_uri": "https://scryfall.com/card/eld/242",
+        "cmc": 0.0,
+        "color_identity": ["U"],
+        "name": "Castle Vantress",
+        "type_line": "Legendary Land",
+        "oracle_text": "Castle Vantress enters the battlefield tapped unless you control an Island.\n{T}: Add {U}.\n{2}{U}{U}, {T}: Scry 2.",
+        "produced_mana": ["U"],
+        "set_type": "expansion"
+    }, {
+        "id": "00000000-0000-0000-0000-000000000003",
+        "scryfall_uri": "https://scryfall.com/card/akh/238",
+        "cmc": 0.0,
+        "color_identity": ["R"],
+        "name": "Arena of Glory",
+        "type_line": "Land",
+        "oracle_text": "Arena of Glory enters the battlefield tapped unless you control a Mountain.\n{T}: Add {R}.\n{R}, {T}, Exert Arena of Glory: Add {R}{R}. If that mana is spent on a creature spell, it gains haste until end of turn.",
+        "produced_mana": ["R"],
+        "set_type": "expansion"
+    }]"#;
+
+    #[test]
+    fn castle_needs_legendary_land() {
+        let index = TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+            .unwrap();
+        let castle = index.get("Castle").unwrap();
+        let cards: CardList = serde_json::from_str(CASTLE_VANTRESS).unwrap();
+        let matches: Vec<_> = cards
+            .cards()
+            .iter()
+            .map(|card| castle.match_rules(card, &CardTraits::from_card(card)))
+            .collect();
+        assert_eq!(matches, [vec![TagSource::EntersTapped], vec![]]);
+    }
+
+    #[test]
+    fn subtag_cycles() {
+        let index = tag_index(
+            r#"
+            [A]
+            subtags = ["B"]
+
+            [B]
+            subtags = ["C", "D"]
+
+            [C]
+            alt-names = ["See"]
+            subtags = ["A"]
+
+            [D]
+            subtags = ["B"]
+            "#,
+        );
+        // B and D list each other, which isn't reported.
+        let cycles: Vec<Vec<_>> = index
+            .subtag_cycles()
+            .iter()
+            .map(|cycle| cycle.iter().map(|tag| tag.name().into_owned()).collect())
+            .collect();
+        assert_eq!(cycles, [["A", "B", "C", "A"]]);
+
+        // Nor is listing each other by alt-name.
+        let index = tag_index(
+            r#"
+            [B]
+            subtags = ["See"]
+
+            [C]
+            alt-names = ["See"]
+            subtags = ["B"]
+            "#,
+        );
+        assert!(index.subtag_cycles().is_empty());
+    }
+
+    #[test]
+    fn shipped_config_has_no_subtag_cycles() {
+        let index = TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+            .unwrap();
+        let cycles: Vec<_> = index
+            .subtag_cycles()
+            .iter()
+            .map(|cycle| join(cycle.iter().map(|tag| tag.name()), " -> "))
+            .collect();
+        assert!(cycles.is_empty(), "{:?}", cycles);
+    }
+
+    static CMC_TAGS: &str = r#"
+        [Expensive]
+        kind = "cost"
+        cmc-min = 6
+
+        [Zero]
+        kind = "cost"
+        cmc = 0
+
+        ["Two to Four"]
+        kind = "cost"
+        cmc-min = 2
+        cmc-max = 4
+
+        [Cheap]
+        kind = "cost"
+        cmc-max = 2
+
+        [Two]
+        kind = "cost"
+        cmc = 2
+    "#;
+
+    fn card_json(name: &str, type_line: &str, cmc: f32) -> String {
+        format!(
+            r#"[{{
+                "id": "1",
+                "scryfall_uri": "https://scryfall.com/card/tst/1",
+                "cmc": {},
+                "color_identity": [],
+                "name": "{}",
+                "type_line": "{}",
+                "set_type": "expansion"
+            }}]"#,
+            cmc, name, type_line
+        )
+    }
+
+    #[test]
+    fn cmc_ranges() {
+        let index = tag_index(CMC_TAGS);
+        let range = |name| index.get(name).unwrap().cmc.unwrap();
+        assert_eq!(CmcRange::new(None, None, None), None);
+        assert_eq!(range("Zero").to_string(), "0");
+        assert_eq!(range("Cheap").to_string(), "≤2");
+        assert_eq!(range("Two to Four").to_string(), "2–4");
+        assert_eq!(range("Expensive").to_string(), "6+");
+        assert_eq!(index.get("Expensive").unwrap().name(), "CMC: 6+");
+
+        assert!(range("Cheap").contains(0.0) && range("Cheap").contains(2.0));
+        assert!(!range("Cheap").contains(2.5));
+        assert!(range("Two to Four").contains(3.0));
+        assert!(!range("Two to Four").contains(1.0) && !range("Two to Four").contains(5.0));
+        assert!(range("Expensive").contains(16.0) && !range("Expensive").contains(5.0));
+        assert!(range("Two").contains(2.0) && !range("Two").contains(3.0));
+    }
+
+    #[test]
+    fn cmc_tags_sort_by_range() {
+        let index = tag_index(CMC_TAGS);
+        let mut tags: Vec<_> = index.iter().map(|(_, tag)| tag).collect();
+        index.kinds[&TagKind::Cost].sort_tags(&mut tags);
+        let names: Vec<_> = tags.iter().map(|tag| tag.name.as_str()).collect();
+        assert_eq!(names, ["Cheap", "Zero", "Two", "Two to Four", "Expensive"]);
+    }
+
+    #[test]
+    fn zero_cost_lands_have_no_cmc_tag() {
+        let index = tag_index(CMC_TAGS);
+        let matches = |name: &str, type_line: &str, cmc: f32| {
+            let json = card_json(name, type_line, cmc);
+            let cards: CardList = serde_json::from_str(&json).unwrap();
+            let card = &cards.cards()[0];
+            let traits = CardTraits::from_card(card);
+            let mut names: Vec<_> = index
+                .iter()
+                .filter(|(_, tag)| tag.is_match(card, &traits))
+                .map(|(name, _)| name.to_owned())
+                .collect();
+            names.sort_unstable();
+            names
+        };
+        assert!(matches("Island", "Basic Land — Island", 0.0).is_empty());
+        assert_eq!(
+            matches("Ornithopter", "Artifact Creature — Thopter", 0.0),
+            ["Cheap", "Zero"]
+        );
+        // A land with a cost is still matched, such as a modal double-faced card's spell side.
+        assert_eq!(
+            matches("Agadeem's Awakening", "Sorcery // Land", 3.0),
+            ["Two to Four"]
+        );
+    }
+
+    // Writes config files to a fresh directory for one test.
+    fn write_config(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("manabase-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for (file, text) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn merge_included_files() {
+        let dir = write_config(
+            "merge",
+            &[
+                (
+                    "tags.toml",
+                    r#"
+                    include = ["lands.toml"]
+
+                    [kinds.land-cycle]
+                    name = "Land Cycle"
+
+                    [Fetch]
+                    "#,
+                ),
+                (
+                    "lands.toml",
+                    r#"
+                    [kinds.cost]
+                    position = 5
+
+                    ["Shock Dual"]
+                    kind = "land-cycle"
+                    "#,
+                ),
+            ],
+        );
+        let index = TagIndex::load(&dir.join("tags.toml")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(index.get("Fetch").is_some());
+        assert_eq!(index.get("Shock Dual").unwrap().kind().name, "Land Cycle");
+        assert_eq!(index.kinds[&TagKind::Cost].position, 5);
+    }
+
+    #[test]
+    fn merge_included_directory() {
+        let dir = write_config(
+            "directory",
+            &[
+                ("card-tags.toml", r#"include = ["card-tags"]"#),
+                ("card-tags/a.toml", r#""Arid Mesa" = ["Fetch"]"#),
+                ("card-tags/b.toml", r#""Blood Crypt" = ["Shock Dual"]"#),
+                ("card-tags/notes.txt", r#""Commit" = ["Not Loaded"]"#),
+            ],
+        );
+        let card_tags = CardTags::load(&dir.join("card-tags.toml")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut cards: Vec<_> = card_tags.cards().collect();
+        cards.sort_unstable();
+        assert_eq!(cards, ["Arid Mesa", "Blood Crypt"]);
+        assert_eq!(card_tags.get_tags("Blood Crypt").unwrap(), ["Shock Dual"]);
+    }
+
+    #[test]
+    fn duplicate_tags_across_files() {
+        let dir = write_config(
+            "duplicate",
+            &[
+                ("tags/a.toml", "[Fetch]\n"),
+                ("tags/b.toml", "[Fetch]\nalt-names = [\"Land Fetch\"]\n"),
+            ],
+        );
+        let error = TagIndex::load(&dir.join("tags")).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "\"Fetch\" is defined in both {} and {}",
+                dir.join("tags/a.toml").display(),
+                dir.join("tags/b.toml").display()
+            )
+        );
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Snow Basic"), "snow-basic");
+        assert_eq!(slugify("Urza's"), "urza-s");
+        assert_eq!(slugify(" -Sac_Land!- "), "sac-land");
+    }
+
+    #[test]
+    fn slug_collisions() {
+        let error = TagIndex::from_config(
+            toml::from_str(
+                r#"
+                ["Sac Land"]
+                ["Sac-Land"]
+                "#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "tags \"Sac Land\", \"Sac-Land\" share the page name \"sac-land\""
+        );
+
+        // Tags only used in card-tags.toml are checked when they're merged in.
+        let mut index = tag_index(r#"["Sac Land"]"#);
+        let card_tags: CardTags = toml::from_str(r#""Evolving Wilds" = ["Sac  Land"]"#).unwrap();
+        let error = index.merge_tags(&card_tags).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "tags \"Sac  Land\", \"Sac Land\" share the page name \"sac-land\""
+        );
+    }
+
+    #[test]
+    fn renamed_tag_redirects() {
+        let mut history: SlugHistory = toml::from_str(
+            r#"
+            fetch = "Fetch"
+            snow-tapland = "Snow Tapland"
+            sphere = "Sphere"
+            "#,
+        )
+        .unwrap();
+        let index = tag_index(
+            r#"
+            [Fetch]
+
+            ["Snow Dual"]
+            alt-names = ["Snow Tapland"]
+            "#,
+        );
+
+        // Sphere was removed, so its page isn't redirected.
+        let redirects: Vec<_> = history
+            .redirects(&index)
+            .into_iter()
+            .map(|(slug, tag)| (slug, tag.name().into_owned()))
+            .collect();
+        assert_eq!(redirects, [("snow-tapland", "Snow Dual".to_owned())]);
+
+        assert!(history.update(&index));
+        assert!(!history.update(&index));
+        assert_eq!(
+            history.0.get("snow-dual").map(String::as_str),
+            Some("Snow Dual")
+        );
+        assert_eq!(
+            history.0.get("snow-tapland").map(String::as_str),
+            Some("Snow Tapland")
+        );
     }
 }
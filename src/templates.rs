@@ -1,21 +1,20 @@
 use crate::{
     card::{CardType, TaggedCard, TaggedCardDb},
-    tags::{TagDb, TagIndex, TagRef},
+    color::{Colors, Mana},
+    cycle::CycleReport,
+    tags::{CycleKind, TagDb, TagIndex, TagKind, TagRef},
 };
 use askama::Template;
 use chrono::prelude::*;
 use log::debug;
-use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    io::Write,
-    path::Path,
-};
+use std::{borrow::Cow, collections::HashSet, fs::File, io::Write, path::Path};
 
 #[derive(Debug, Template)]
 #[template(path = "index.html")]
 pub struct IndexPage<'a> {
-    card_types: [CardType; 8],
+    card_types: [CardType; 9],
+    cycle_kinds: [CycleKind; 2],
+    all_colors: Vec<Colors>,
     tagdb: &'a TagDb<'a>,
     carddb: &'a TaggedCardDb<'a>,
     timestamp: DateTime<Utc>,
@@ -52,15 +51,75 @@ pub struct TypePage<'a> {
 #[derive(Debug, Template)]
 #[template(path = "tag-cards.html")]
 pub struct TagPage<'a> {
-    card_types: [CardType; 8],
+    card_types: [CardType; 9],
     tag: TagRef<'a>,
-    tag_index: &'a TagIndex,
-    cards: HashMap<Option<TagRef<'a>>, Vec<&'a TaggedCard<'a>>>,
+    tagdb: &'a TagDb<'a>,
+    sections: Vec<TagSection<'a>>,
     carddb: &'a TaggedCardDb<'a>,
+    can_fetch: Vec<&'a TaggedCard<'a>>,
+    fetchable_by: Vec<&'a TaggedCard<'a>>,
+    cycle: Option<CycleReport<'a, 'a>>,
+    timestamp: DateTime<Utc>,
+    data_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Template)]
+#[template(path = "card-page.html")]
+pub struct CardPage<'a> {
+    card: &'a TaggedCard<'a>,
+    can_fetch: Vec<&'a TaggedCard<'a>>,
+    fetchable_by: Vec<&'a TaggedCard<'a>>,
+    timestamp: DateTime<Utc>,
+    data_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Template)]
+#[template(path = "cycle-matrix.html")]
+pub struct CycleMatrixPage<'a> {
+    cycle_kind: CycleKind,
+    columns: Vec<TagRef<'a>>,
+    rows: Vec<MatrixRow<'a>>,
+    timestamp: DateTime<Utc>,
+    data_updated: DateTime<Utc>,
+}
+
+#[derive(Debug, Template)]
+#[template(path = "colors.html")]
+pub struct ColorsPage<'a> {
+    colors: Colors,
+    mana_tag: Option<TagRef<'a>>,
+    cards: Vec<&'a TaggedCard<'a>>,
     timestamp: DateTime<Utc>,
     data_updated: DateTime<Utc>,
 }
 
+#[derive(Debug, Template)]
+#[template(path = "redirect.html")]
+pub struct RedirectPage<'a> {
+    slug: &'a str,
+    tag: TagRef<'a>,
+}
+
+/// One heading of a tag page: a subtag at some depth of the subtag tree, or
+/// the cards not covered by any subtag when `tag` is `None`.
+#[derive(Debug)]
+pub struct TagSection<'a> {
+    tag: Option<TagRef<'a>>,
+    depth: usize,
+    anchor: String,
+    card_types: HashSet<CardType>,
+    cards: Vec<&'a TaggedCard<'a>>,
+}
+
+/// One color combination of a cycle matrix, with the cards of each cycle tag that are also in the
+/// combination's mana pool tag.
+#[derive(Debug)]
+pub struct MatrixRow<'a> {
+    colors: Colors,
+    mana_tag: Option<TagRef<'a>>,
+    cells: Vec<Vec<&'a TaggedCard<'a>>>,
+}
+
 impl<'a> IndexPage<'a> {
     pub fn new(
         tagdb: &'a TagDb<'a>,
@@ -71,6 +130,7 @@ impl<'a> IndexPage<'a> {
         IndexPage {
             card_types: [
                 CardType::Land,
+                CardType::ModalLand,
                 CardType::Artifact,
                 CardType::Creature,
                 CardType::Enchantment,
@@ -79,6 +139,8 @@ impl<'a> IndexPage<'a> {
                 CardType::Planeswalker,
                 CardType::Battle,
             ],
+            cycle_kinds: [CycleKind::Dual, CycleKind::Tri],
+            all_colors: Colors::all(),
             tagdb,
             carddb,
             timestamp,
@@ -172,16 +234,12 @@ impl<'a> TagPage<'a> {
     pub fn new(
         tag: TagRef<'a>,
         tag_index: &'a TagIndex,
+        tagdb: &'a TagDb<'a>,
         carddb: &'a TaggedCardDb<'a>,
         timestamp: DateTime<Utc>,
         data_updated: DateTime<Utc>,
     ) -> TagPage<'a> {
-        let subtags: HashSet<_> = tag
-            .subtags()
-            .iter()
-            .filter_map(|name| tag_index.get(name))
-            .collect();
-        let cards: Vec<_> = carddb
+        let mut cards: Vec<_> = carddb
             .tag_index()
             .get(&tag)
             .map(|ids| {
@@ -190,28 +248,25 @@ impl<'a> TagPage<'a> {
                     .collect()
             })
             .unwrap_or_default();
-        let mut tag_map: HashMap<_, Vec<_>> = HashMap::new();
-        for subtag in subtags.iter() {
-            let mut tagcards: Vec<_> = cards
-                .iter()
-                .map(|card| *card)
-                .filter(|card| card.tag_set().contains(subtag))
-                .collect();
-            tagcards.sort_unstable_by_key(|card| &card.card().name);
-            tag_map.insert(Some(*subtag), tagcards);
-        }
-        let mut untagged: Vec<_> = cards
-            .iter()
-            .map(|card| *card)
-            .filter(|card| card.tag_set().is_disjoint(&subtags))
-            .collect();
-        untagged.sort_unstable_by_key(|card| &card.card().name);
+        cards.sort_unstable_by_key(|card| &card.card().name);
+        let mut sections = Vec::new();
+        let untagged = add_subtag_sections(tag_index, tag, &cards, &mut vec![], "", &mut sections);
         if !untagged.is_empty() {
-            tag_map.insert(None, untagged);
+            sections.push(TagSection {
+                tag: None,
+                depth: 1,
+                anchor: "Other".to_owned(),
+                card_types: untagged
+                    .iter()
+                    .flat_map(|card| card.types().iter().copied())
+                    .collect(),
+                cards: untagged,
+            });
         }
         TagPage {
             card_types: [
                 CardType::Land,
+                CardType::ModalLand,
                 CardType::Artifact,
                 CardType::Creature,
                 CardType::Enchantment,
@@ -221,9 +276,12 @@ impl<'a> TagPage<'a> {
                 CardType::Battle,
             ],
             tag,
-            tag_index,
-            cards: tag_map,
+            tagdb,
+            sections,
             carddb,
+            can_fetch: carddb.tag_can_fetch(&tag),
+            fetchable_by: carddb.tag_fetchable_by(&tag),
+            cycle: CycleReport::new(&tag, carddb),
             timestamp,
             data_updated,
         }
@@ -233,39 +291,432 @@ impl<'a> TagPage<'a> {
         &self.tag
     }
 
-    pub fn subtags(&self) -> Vec<TagRef<'a>> {
-        self.tag
-            .subtags()
+    pub fn parents(&self) -> &[TagRef<'a>] {
+        self.tagdb.parents(&self.tag)
+    }
+
+    pub fn write_output(&self, output_dir: &Path) -> std::io::Result<()> {
+        write!(
+            File::create(output_dir.join(format!("tag-{}.html", self.tag.slug())))?,
+            "{}",
+            self
+        )
+    }
+}
+
+impl<'a> CardPage<'a> {
+    pub fn new(
+        card: &'a TaggedCard<'a>,
+        carddb: &'a TaggedCardDb<'a>,
+        timestamp: DateTime<Utc>,
+        data_updated: DateTime<Utc>,
+    ) -> CardPage<'a> {
+        CardPage {
+            card,
+            can_fetch: carddb.can_fetch(card),
+            fetchable_by: carddb.fetchable_by(card),
+            timestamp,
+            data_updated,
+        }
+    }
+
+    pub fn write_output(&self, output_dir: &Path) -> std::io::Result<()> {
+        write!(
+            File::create(output_dir.join(format!("card-{}.html", self.card.slug())))?,
+            "{}",
+            self
+        )
+    }
+}
+
+impl<'a> CycleMatrixPage<'a> {
+    pub fn new(
+        cycle_kind: CycleKind,
+        tag_index: &'a TagIndex,
+        carddb: &'a TaggedCardDb<'a>,
+        timestamp: DateTime<Utc>,
+        data_updated: DateTime<Utc>,
+    ) -> CycleMatrixPage<'a> {
+        let mut columns: Vec<_> = tag_index
             .iter()
-            .filter_map(|name| self.tag_index.get(name))
-            .collect()
+            .map(|(_, tag)| tag)
+            .filter(|tag| tag.cycle() == Some(cycle_kind))
+            .collect();
+        columns.sort_unstable_by(|a, b| a.name().cmp(&b.name()));
+        let rows = cycle_kind
+            .combinations()
+            .into_iter()
+            .map(|colors| {
+                let mana_tag = mana_pool_tag(tag_index, colors);
+                let cells = columns
+                    .iter()
+                    .map(|column| {
+                        let mut cards: Vec<_> = match mana_tag {
+                            Some(mana_tag) => carddb
+                                .tag_index()
+                                .get(column)
+                                .into_iter()
+                                .flatten()
+                                .filter(|id| {
+                                    carddb
+                                        .tag_index()
+                                        .get(&mana_tag)
+                                        .filter(|ids| ids.contains(id))
+                                        .is_some()
+                                })
+                                .filter_map(|id| carddb.card_index().get(id))
+                                .collect(),
+                            None => Vec::new(),
+                        };
+                        cards.sort_unstable_by_key(|card| &card.card().name);
+                        cards
+                    })
+                    .collect();
+                MatrixRow {
+                    colors,
+                    mana_tag,
+                    cells,
+                }
+            })
+            .collect();
+        CycleMatrixPage {
+            cycle_kind,
+            columns,
+            rows,
+            timestamp,
+            data_updated,
+        }
     }
 
-    pub fn get_tag_cards(&self, tag: TagRef<'a>) -> Option<&Vec<&'a TaggedCard<'a>>> {
-        self.cards.get(&Some(tag.clone()))
+    pub fn write_output(&self, output_dir: &Path) -> std::io::Result<()> {
+        write!(
+            File::create(output_dir.join(format!("cycles-{}.html", self.cycle_kind.slug())))?,
+            "{}",
+            self
+        )
+    }
+}
+
+impl<'a> ColorsPage<'a> {
+    /// Lists the tagged lands whose mana is exactly a combination of colors. Lands that make no
+    /// mana, such as fetches, aren't on any page, and the colorless page only has lands making
+    /// colorless mana.
+    pub fn new(
+        colors: Colors,
+        tag_index: &'a TagIndex,
+        carddb: &'a TaggedCardDb<'a>,
+        timestamp: DateTime<Utc>,
+        data_updated: DateTime<Utc>,
+    ) -> ColorsPage<'a> {
+        let mut cards: Vec<_> = carddb
+            .cards()
+            .filter(|card| card.has_type(&CardType::Land) || card.has_type(&CardType::ModalLand))
+            .filter(|card| {
+                let produced = card.card().produced_colors();
+                if produced.is_empty() {
+                    colors == Colors::Colorless && card.card().produces_colorless()
+                } else {
+                    Colors::from_vec(produced) == colors
+                }
+            })
+            .collect();
+        cards.sort_unstable_by_key(|card| &card.card().name);
+        ColorsPage {
+            colors,
+            mana_tag: mana_pool_tag(tag_index, colors),
+            cards,
+            timestamp,
+            data_updated,
+        }
+    }
+
+    pub fn write_output(&self, output_dir: &Path) -> std::io::Result<()> {
+        write!(
+            File::create(output_dir.join(format!("colors-{}.html", self.colors.slug())))?,
+            "{}",
+            self
+        )
+    }
+}
+
+impl<'a> MatrixRow<'a> {
+    pub fn colors(&self) -> Colors {
+        self.colors
     }
 
-    pub fn get_untagged_cards(&self) -> Option<&Vec<&'a TaggedCard<'a>>> {
-        self.cards.get(&None)
+    pub fn mana_tag(&self) -> Option<&TagRef<'a>> {
+        self.mana_tag.as_ref()
     }
 
-    pub fn subtag_has_cards_of_type(&self, tag: &TagRef<'a>, card_type: &CardType) -> bool {
-        self.get_tag_cards(*tag)
-            .map(|vec| vec.iter().any(|card| card.has_type(card_type)))
-            .unwrap_or_default()
+    pub fn cells(&self) -> &[Vec<&'a TaggedCard<'a>>] {
+        &self.cells
     }
+}
 
-    pub fn has_untagged_cards_of_type(&self, card_type: &CardType) -> bool {
-        self.get_untagged_cards()
-            .map(|vec| vec.iter().any(|card| card.has_type(card_type)))
-            .unwrap_or_default()
+impl<'a> RedirectPage<'a> {
+    pub fn new(slug: &'a str, tag: TagRef<'a>) -> RedirectPage<'a> {
+        RedirectPage { slug, tag }
     }
 
     pub fn write_output(&self, output_dir: &Path) -> std::io::Result<()> {
         write!(
-            File::create(output_dir.join(format!("tag-{}.html", self.tag.canonical_name())))?,
+            File::create(output_dir.join(format!("tag-{}.html", self.slug)))?,
             "{}",
             self
         )
     }
 }
+
+impl<'a> TagSection<'a> {
+    pub fn tag(&self) -> Option<&TagRef<'a>> {
+        self.tag.as_ref()
+    }
+
+    pub fn name(&self) -> Cow<'_, str> {
+        self.tag
+            .as_ref()
+            .map_or(Cow::Borrowed("Other"), |tag| tag.name())
+    }
+
+    pub fn anchor(&self) -> &str {
+        &self.anchor
+    }
+
+    pub fn cards(&self) -> &[&'a TaggedCard<'a>] {
+        &self.cards
+    }
+
+    pub fn has_cards_of_type(&self, card_type: &CardType) -> bool {
+        self.card_types.contains(card_type)
+    }
+
+    pub fn heading_level(&self) -> usize {
+        (self.depth + 3).min(6)
+    }
+
+    pub fn indent(&self) -> usize {
+        (self.depth - 1).min(5)
+    }
+}
+
+/// Adds a section for each subtag of `tag` that has any of `cards`, followed
+/// by the sections of its own subtags, and returns the cards not covered by
+/// any subtag. Subtags already on `path` are skipped to break cycles.
+fn add_subtag_sections<'a>(
+    tag_index: &'a TagIndex,
+    tag: TagRef<'a>,
+    cards: &[&'a TaggedCard<'a>],
+    path: &mut Vec<TagRef<'a>>,
+    anchor: &str,
+    sections: &mut Vec<TagSection<'a>>,
+) -> Vec<&'a TaggedCard<'a>> {
+    let mut covered = HashSet::new();
+    path.push(tag);
+    for subtag in tag.subtags().iter().filter_map(|name| tag_index.get(name)) {
+        if path.contains(&subtag) {
+            continue;
+        }
+        let subcards: Vec<_> = cards
+            .iter()
+            .copied()
+            .filter(|card| card.tag_set().contains(&subtag))
+            .collect();
+        if subcards.is_empty() {
+            continue;
+        }
+        covered.extend(subcards.iter().map(|card| card.card().id.as_ref()));
+        let subanchor = if anchor.is_empty() {
+            subtag.slug().to_owned()
+        } else {
+            format!("{}-{}", anchor, subtag.slug())
+        };
+        let index = sections.len();
+        sections.push(TagSection {
+            tag: Some(subtag),
+            depth: path.len(),
+            anchor: subanchor.clone(),
+            card_types: subcards
+                .iter()
+                .flat_map(|card| card.types().iter().copied())
+                .collect(),
+            cards: vec![],
+        });
+        sections[index].cards =
+            add_subtag_sections(tag_index, subtag, &subcards, path, &subanchor, sections);
+    }
+    path.pop();
+    cards
+        .iter()
+        .copied()
+        .filter(|card| !covered.contains(card.card().id.as_ref()))
+        .collect()
+}
+
+/// The mana pool tag whose `mana` rule is exactly a combination of colors.
+fn mana_pool_tag(tag_index: &TagIndex, colors: Colors) -> Option<TagRef<'_>> {
+    tag_index.iter().map(|(_, tag)| tag).find(|tag| {
+        *tag.kind().kind() == TagKind::ManaPool && tag.mana() == Some(Mana::from(colors))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, scryfall::CardList, tags::CardTags};
+
+    static CARDS: &str = r#"[
+        {
+            "id": "1",
+            "scryfall_uri": "https://scryfall.com/card/ons/321",
+            "cmc": 0.0,
+            "color_identity": [],
+            "name": "Polluted Delta",
+            "type_line": "Land",
+            "oracle_text": "{T}, Pay 1 life, Sacrifice Polluted Delta: Search your library for an Island or Swamp card, put it onto the battlefield, then shuffle.",
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/delta.jpg"}
+        },
+        {
+            "id": "2",
+            "scryfall_uri": "https://scryfall.com/card/ogw/183",
+            "cmc": 0.0,
+            "color_identity": [],
+            "name": "Wastes",
+            "type_line": "Basic Land",
+            "oracle_text": "({T}: Add {C}.)",
+            "produced_mana": ["C"],
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/wastes.jpg"}
+        },
+        {
+            "id": "3",
+            "scryfall_uri": "https://scryfall.com/card/lea/290",
+            "cmc": 0.0,
+            "color_identity": ["U"],
+            "name": "Island",
+            "type_line": "Basic Land — Island",
+            "oracle_text": "({T}: Add {U}.)",
+            "produced_mana": ["U"],
+            "set_type": "core",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/island.jpg"}
+        }
+    ]"#;
+
+    static CARD_TAGS: &str = r#"
+        "Polluted Delta" = ["Fetch"]
+        "Wastes" = ["Colorless Mana"]
+        "Island" = ["Blue Mana", "Basic"]
+    "#;
+
+    #[test]
+    fn lands_without_mana_are_on_no_colors_page() {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let card_tags: CardTags = toml::from_str(CARD_TAGS).unwrap();
+        let cards: CardList = serde_json::from_str(CARDS).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        assert!(carddb.find_by_name("Polluted Delta").is_some());
+
+        let names = |colors| {
+            let page = ColorsPage::new(colors, &tag_index, &carddb, Utc::now(), Utc::now());
+            page.cards
+                .iter()
+                .map(|card| card.card().name.as_ref())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(Colors::Colorless), ["Wastes"]);
+        assert_eq!(names(Colors::Mono(Color::Blue)), ["Island"]);
+        assert!(Colors::all()
+            .into_iter()
+            .all(|colors| !names(colors).contains(&"Polluted Delta")));
+    }
+
+    static CYCLE_TAGS: &str = r#"
+        [A]
+        subtags = ["B"]
+
+        [B]
+        subtags = ["C", "A"]
+
+        [C]
+        subtags = ["B"]
+    "#;
+
+    static CYCLE_CARDS: &str = r#"[
+        {
+            "id": "1",
+            "scryfall_uri": "https://scryfall.com/card/tst/1",
+            "cmc": 0.0,
+            "color_identity": [],
+            "name": "Every Tag",
+            "type_line": "Land",
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/1.jpg"}
+        },
+        {
+            "id": "2",
+            "scryfall_uri": "https://scryfall.com/card/tst/2",
+            "cmc": 0.0,
+            "color_identity": [],
+            "name": "Only A",
+            "type_line": "Land",
+            "set_type": "expansion",
+            "image_uris": {"normal": "https://cards.scryfall.io/normal/2.jpg"}
+        }
+    ]"#;
+
+    #[test]
+    fn nested_sections_stop_at_cycles() {
+        let config = std::env::temp_dir().join(format!("cycle-tags-{}.toml", std::process::id()));
+        std::fs::write(&config, CYCLE_TAGS).unwrap();
+        let tag_index = TagIndex::load(&config).unwrap();
+        std::fs::remove_file(&config).unwrap();
+        let card_tags: CardTags = toml::from_str(
+            r#""Every Tag" = ["A", "B", "C"]
+"Only A" = ["A"]"#,
+        )
+        .unwrap();
+        let cards: CardList = serde_json::from_str(CYCLE_CARDS).unwrap();
+        let carddb = TaggedCardDb::new(&card_tags, &tag_index, &cards);
+        let tagdb = TagDb::new(&tag_index);
+
+        let tag = tag_index.get("A").unwrap();
+        let page = TagPage::new(tag, &tag_index, &tagdb, &carddb, Utc::now(), Utc::now());
+        let sections: Vec<_> = page
+            .sections
+            .iter()
+            .map(|section| {
+                (
+                    section.anchor.as_str(),
+                    section.depth,
+                    section
+                        .cards
+                        .iter()
+                        .map(|card| card.card().name.as_ref())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        // C's subtag B and B's subtag A are already on the page, so nesting stops at C.
+        assert_eq!(
+            sections,
+            [
+                ("b", 1, vec![]),
+                ("b-c", 2, vec!["Every Tag"]),
+                ("Other", 1, vec!["Only A"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn redirect_page_links_to_renamed_tag() {
+        let tag_index =
+            TagIndex::load(&Path::new(env!("CARGO_MANIFEST_DIR")).join("config/tags.toml"))
+                .unwrap();
+        let tag = tag_index.get("Snow Tapland").unwrap();
+        let html = RedirectPage::new("snow-tapland", tag).to_string();
+        assert!(html.contains(r#"<meta http-equiv="refresh" content="0; url=tag-snow-dual.html">"#));
+        assert!(html.contains(r#"<a href="tag-snow-dual.html">Snow Dual</a>"#));
+    }
+}